@@ -1,6 +1,6 @@
 use std::ops::Add;
 
-use crate::{Interval, Point3, Ray, Vec3};
+use crate::{Error, Interval, Point3, Ray, Result, Vec3};
 
 #[derive(Debug, Default, Clone)]
 pub struct Aabb {
@@ -29,13 +29,28 @@ impl Aabb {
         }
     }
 
-    pub fn axis(&self, axis: usize) -> &Interval {
-        match axis {
-            0 => &self.x,
-            1 => &self.y,
-            2 => &self.z,
-            _ => panic!("Invalid axis"),
-        }
+    pub fn axis(&self, axis: usize) -> Result<&Interval> {
+        self.axes()
+            .get(axis)
+            .copied()
+            .ok_or(Error::InvalidAxis(axis))
+    }
+
+    pub fn axes(&self) -> [&Interval; 3] {
+        [&self.x, &self.y, &self.z]
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        Point3::new(
+            (self.x.min + self.x.max) / 2.0,
+            (self.y.min + self.y.max) / 2.0,
+            (self.z.min + self.z.max) / 2.0,
+        )
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let (dx, dy, dz) = (self.x.size(), self.y.size(), self.z.size());
+        2.0 * (dx * dy + dy * dz + dz * dx)
     }
 
     pub fn pad(&self) -> Self {
@@ -61,10 +76,9 @@ impl Aabb {
     pub fn hit(&self, ray: &Ray, interval: &Interval) -> bool {
         let mut t_min = interval.min;
         let mut t_max = interval.max;
-        for i in 0..3 {
+        for (i, axis) in self.axes().into_iter().enumerate() {
             let inv_d = 1.0 / ray.dir()[i];
             let origin = ray.origin()[i];
-            let axis = self.axis(i);
             let mut t0 = (axis.min - origin) * inv_d;
             let mut t1 = (axis.max - origin) * inv_d;
             if inv_d < 0.0 {
@@ -0,0 +1,298 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use rtracer::{IntegratorKind, LightSampling, SamplerKind};
+
+pub const USAGE: &str = "\
+Usage: main [OPTIONS]
+
+Options:
+  -s, --scene <NAME>     Built-in scene to render [default: final_scene]
+  -f, --file <PATH>      Load a TOML scene file instead of a built-in scene
+  -l, --list             List the built-in scenes and exit
+  -w, --width <PIXELS>   Image width
+  -a, --aspect <RATIO>   Aspect ratio, either `1.5` or `16:9`
+      --spp <N>          Samples per pixel
+  -d, --depth <N>        Maximum ray bounce depth
+      --seed <N>         Seed for scene generation and sampling [default: 0]
+  -t, --threads <N>      Worker threads [default: all cores]
+  -i, --integrator <NAME>
+                         How pixels are shaded: `path`, `whitted`, `ao`,
+                         `normals`, `albedo` or `depth` [default: path]
+      --ao-radius <DIST> Occlusion radius for `ao` [default: a tenth of
+                         the scene's size]
+      --noise-threshold <X>
+                         Stop sampling a pixel once its noise, in display
+                         units, is below X; `--spp` becomes the cap
+      --min-spp <N>      Samples before a pixel may stop [default: 16]
+      --sample-map <PATH>
+                         Also write the samples taken per pixel as an
+                         image
+      --sampler <NAME>   Sample sequence: `independent`, `stratified`,
+                         `halton`, `sobol` or `blue_noise`
+                         [default: independent]
+      --light-sampling <MODE>
+                         How diffuse bounces reach lights: `mis` or
+                         `mixture` [default: mis]
+      --roulette-depth <N>
+                         Bounces before Russian roulette may end a path
+                         [default: 5]
+      --stats            Print path statistics after rendering
+  -o, --output <PATH>    Output file; the extension picks the format
+                         (png, jpg, ppm, exr, hdr, pfm). ASCII PPM goes to
+                         stdout when omitted.
+      --png16            Write 16 bits per channel to a PNG output
+      --jpeg-quality <N> Quality of a JPEG output, 1 to 100 [default: 90]
+  -h, --help             Print this help and exit";
+
+/// What the command line asked for. `None` keeps the scene's own value.
+#[derive(Debug, Default, PartialEq)]
+pub struct Options {
+    pub scene: Option<String>,
+    pub file: Option<PathBuf>,
+    pub list: bool,
+    pub help: bool,
+    pub width: Option<usize>,
+    pub aspect_ratio: Option<f64>,
+    pub sample_per_pixel: Option<usize>,
+    pub max_depth: Option<usize>,
+    pub seed: u64,
+    pub threads: Option<usize>,
+    pub integrator: Option<IntegratorKind>,
+    pub ao_radius: Option<f64>,
+    pub noise_threshold: Option<f64>,
+    pub min_sample_per_pixel: Option<usize>,
+    pub sample_map: Option<PathBuf>,
+    pub sampler: Option<SamplerKind>,
+    pub light_sampling: Option<LightSampling>,
+    pub roulette_min_depth: Option<usize>,
+    pub stats: bool,
+    pub output: Option<PathBuf>,
+    pub png16: bool,
+    pub jpeg_quality: Option<u8>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CliError {
+    UnknownOption(String),
+    MissingValue(&'static str),
+    Conflict(&'static str, &'static str),
+    InvalidValue { option: &'static str, value: String },
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::UnknownOption(arg) => write!(f, "unknown argument `{}`", arg),
+            CliError::MissingValue(option) => write!(f, "`{}` needs a value", option),
+            CliError::Conflict(a, b) => write!(f, "`{}` cannot be used with `{}`", a, b),
+            CliError::InvalidValue { option, value } => {
+                write!(f, "invalid value `{}` for `{}`", value, option)
+            }
+        }
+    }
+}
+
+impl Options {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, CliError> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            // Accept both `--width 400` and `--width=400`.
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => {
+                    (flag.to_string(), Some(value.to_string()))
+                }
+                _ => (arg.clone(), None),
+            };
+            let mut value = |option: &'static str| {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or(CliError::MissingValue(option))
+            };
+            match flag.as_str() {
+                "-h" | "--help" => options.help = true,
+                "-l" | "--list" => options.list = true,
+                "-s" | "--scene" => options.scene = Some(value("--scene")?),
+                "-f" | "--file" => options.file = Some(PathBuf::from(value("--file")?)),
+                "-w" | "--width" => {
+                    options.width = Some(parse_positive("--width", value("--width")?)?)
+                }
+                "-a" | "--aspect" => options.aspect_ratio = Some(parse_aspect(value("--aspect")?)?),
+                "--spp" => {
+                    options.sample_per_pixel = Some(parse_positive("--spp", value("--spp")?)?)
+                }
+                "-d" | "--depth" => {
+                    options.max_depth = Some(parse_positive("--depth", value("--depth")?)?)
+                }
+                "--seed" => options.seed = parse("--seed", value("--seed")?)?,
+                "-t" | "--threads" => {
+                    options.threads = Some(parse_positive("--threads", value("--threads")?)?)
+                }
+                "-i" | "--integrator" => {
+                    options.integrator = Some(parse("--integrator", value("--integrator")?)?)
+                }
+                "--ao-radius" => {
+                    options.ao_radius =
+                        Some(parse_positive_f64("--ao-radius", value("--ao-radius")?)?)
+                }
+                "--noise-threshold" => {
+                    options.noise_threshold = Some(parse_positive_f64(
+                        "--noise-threshold",
+                        value("--noise-threshold")?,
+                    )?)
+                }
+                "--min-spp" => {
+                    options.min_sample_per_pixel =
+                        Some(parse_positive("--min-spp", value("--min-spp")?)?)
+                }
+                "--sample-map" => options.sample_map = Some(PathBuf::from(value("--sample-map")?)),
+                "--sampler" => options.sampler = Some(parse("--sampler", value("--sampler")?)?),
+                "--light-sampling" => {
+                    options.light_sampling =
+                        Some(parse("--light-sampling", value("--light-sampling")?)?)
+                }
+                "--roulette-depth" => {
+                    options.roulette_min_depth =
+                        Some(parse("--roulette-depth", value("--roulette-depth")?)?)
+                }
+                "--stats" => options.stats = true,
+                "-o" | "--output" => options.output = Some(PathBuf::from(value("--output")?)),
+                "--png16" => options.png16 = true,
+                "--jpeg-quality" => {
+                    options.jpeg_quality = Some(parse_quality(value("--jpeg-quality")?)?)
+                }
+                _ => return Err(CliError::UnknownOption(arg)),
+            }
+        }
+        if options.scene.is_some() && options.file.is_some() {
+            return Err(CliError::Conflict("--scene", "--file"));
+        }
+        Ok(options)
+    }
+}
+
+fn parse<T: FromStr>(option: &'static str, value: String) -> Result<T, CliError> {
+    value
+        .parse()
+        .map_err(|_| CliError::InvalidValue { option, value })
+}
+
+fn parse_positive(option: &'static str, value: String) -> Result<usize, CliError> {
+    match value.parse() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(CliError::InvalidValue { option, value }),
+    }
+}
+
+fn parse_positive_f64(option: &'static str, value: String) -> Result<f64, CliError> {
+    match value.parse::<f64>() {
+        Ok(d) if d.is_finite() && d > 0.0 => Ok(d),
+        _ => Err(CliError::InvalidValue { option, value }),
+    }
+}
+
+fn parse_quality(value: String) -> Result<u8, CliError> {
+    match value.parse() {
+        Ok(q @ 1..=100) => Ok(q),
+        _ => Err(CliError::InvalidValue {
+            option: "--jpeg-quality",
+            value,
+        }),
+    }
+}
+
+fn parse_aspect(value: String) -> Result<f64, CliError> {
+    let ratio = match value.split_once(':') {
+        Some((w, h)) => w
+            .parse::<f64>()
+            .ok()
+            .zip(h.parse::<f64>().ok())
+            .map(|(w, h)| w / h),
+        None => value.parse().ok(),
+    };
+    match ratio {
+        Some(r) if r.is_finite() && r > 0.0 => Ok(r),
+        _ => Err(CliError::InvalidValue {
+            option: "--aspect",
+            value,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CliError, IntegratorKind, LightSampling, Options, SamplerKind};
+
+    fn parse(args: &[&str]) -> Result<Options, CliError> {
+        Options::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn overrides() {
+        let options = parse(&[
+            "--scene",
+            "cornell_box",
+            "-w",
+            "200",
+            "--aspect=16:9",
+            "--spp",
+            "10",
+            "--seed",
+            "3",
+            "--light-sampling=mixture",
+            "-i",
+            "ao",
+            "--ao-radius",
+            "50",
+            "--sampler=sobol",
+            "--noise-threshold",
+            "0.01",
+            "-o",
+            "out.png",
+            "--png16",
+            "--jpeg-quality=75",
+        ])
+        .unwrap();
+        assert_eq!(options.scene.as_deref(), Some("cornell_box"));
+        assert_eq!(options.width, Some(200));
+        assert_eq!(options.aspect_ratio, Some(16.0 / 9.0));
+        assert_eq!(options.sample_per_pixel, Some(10));
+        assert_eq!(options.seed, 3);
+        assert_eq!(options.light_sampling, Some(LightSampling::Mixture));
+        assert_eq!(options.integrator, Some(IntegratorKind::AmbientOcclusion));
+        assert_eq!(options.ao_radius, Some(50.0));
+        assert_eq!(options.sampler, Some(SamplerKind::Sobol));
+        assert_eq!(options.noise_threshold, Some(0.01));
+        assert_eq!(options.output.unwrap().to_str(), Some("out.png"));
+        assert!(options.png16);
+        assert_eq!(options.jpeg_quality, Some(75));
+    }
+
+    #[test]
+    fn bad_arguments() {
+        assert_eq!(
+            parse(&["--bogus"]),
+            Err(CliError::UnknownOption("--bogus".to_string()))
+        );
+        assert_eq!(parse(&["--spp"]), Err(CliError::MissingValue("--spp")));
+        assert_eq!(
+            parse(&["-w", "0"]),
+            Err(CliError::InvalidValue {
+                option: "--width",
+                value: "0".to_string()
+            })
+        );
+        assert!(parse(&["--aspect", "16:0"]).is_err());
+        assert!(parse(&["--integrator", "raster"]).is_err());
+        assert!(parse(&["--ao-radius", "-1"]).is_err());
+        assert!(parse(&["--jpeg-quality", "0"]).is_err());
+        assert!(parse(&["--jpeg-quality", "101"]).is_err());
+        assert_eq!(
+            parse(&["-s", "earth", "-f", "a.toml"]),
+            Err(CliError::Conflict("--scene", "--file"))
+        );
+    }
+}
@@ -0,0 +1,175 @@
+mod cli;
+mod scenes;
+
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use cli::{Options, USAGE};
+use rtracer::{
+    load_scene, FileSink, IntegratorKind, PathStatsSummary, Pcg32, PpmSink, Scene, Sink,
+};
+
+const DEFAULT_SCENE: &str = "final_scene";
+
+fn main() -> ExitCode {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    if options.help {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    if options.list {
+        for (name, _) in scenes::SCENES {
+            println!("{}", name);
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    // Build the sink first so a bad output path fails before rendering.
+    let mut sink: Box<dyn Sink> = match &options.output {
+        Some(path) => match output_sink(path, &options) {
+            Ok(sink) => Box::new(sink),
+            Err(err) => {
+                eprintln!("error: {}", err);
+                return ExitCode::from(2);
+            }
+        },
+        None if options.png16 || options.jpeg_quality.is_some() => {
+            eprintln!("error: `--png16` and `--jpeg-quality` need an `--output` file");
+            return ExitCode::from(2);
+        }
+        None => Box::new(PpmSink::stdout()),
+    };
+
+    let mut sample_map = match &options.sample_map {
+        Some(path) => match FileSink::new(path) {
+            Ok(sink) => Some(sink),
+            Err(err) => {
+                eprintln!("error: {}", err);
+                return ExitCode::from(2);
+            }
+        },
+        None => None,
+    };
+
+    let mut rng = Pcg32::new(options.seed, 0);
+    let Scene {
+        world,
+        lights,
+        camera: mut config,
+        warnings,
+    } = if let Some(path) = &options.file {
+        match load_scene(path, &mut rng) {
+            Ok(scene) => scene,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    } else {
+        let scene_name = options.scene.as_deref().unwrap_or(DEFAULT_SCENE);
+        let Some(scene) = scenes::find(scene_name) else {
+            eprintln!(
+                "error: unknown scene `{}`, `--list` shows the available ones",
+                scene_name
+            );
+            return ExitCode::from(2);
+        };
+        match scene(&mut rng) {
+            Ok(scene) => scene,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    };
+    for warning in &warnings {
+        eprintln!("warning: {}", warning);
+    }
+    if let Some(width) = options.width {
+        config.image_width = width;
+    }
+    if let Some(aspect_ratio) = options.aspect_ratio {
+        config.aspect_ratio = aspect_ratio;
+    }
+    if let Some(sample_per_pixel) = options.sample_per_pixel {
+        config.sample_per_pixel = sample_per_pixel;
+    }
+    if let Some(max_depth) = options.max_depth {
+        config.max_depth = max_depth;
+    }
+    if let Some(noise_threshold) = options.noise_threshold {
+        config.noise_threshold = Some(noise_threshold);
+    }
+    if let Some(min_sample_per_pixel) = options.min_sample_per_pixel {
+        config.min_sample_per_pixel = min_sample_per_pixel;
+    }
+    if let Some(sampler) = options.sampler {
+        config.sampler = sampler;
+    }
+    if let Some(light_sampling) = options.light_sampling {
+        config.light_sampling = light_sampling;
+    }
+    if let Some(roulette_min_depth) = options.roulette_min_depth {
+        config.roulette_min_depth = roulette_min_depth;
+    }
+    if let Some(integrator) = options.integrator {
+        config.integrator = integrator;
+    }
+    if let Some(ao_radius) = options.ao_radius {
+        config.ao_radius = Some(ao_radius);
+    }
+    let mut cam = config.build().with_seed(options.seed);
+    if let Some(threads) = options.threads {
+        cam = cam.with_threads(threads);
+    }
+
+    let stats = options.stats.then(|| Arc::new(PathStatsSummary::new()));
+    if let Some(stats) = &stats {
+        if config.integrator == IntegratorKind::Path {
+            cam = cam.with_integrator(Arc::new(config.path_tracer().with_stats(stats.clone())));
+        } else {
+            eprintln!("warning: `--stats` only counts paths of the path tracer");
+        }
+    }
+
+    let framebuffer = cam.render(&world, &lights);
+    if let Some(stats) = stats {
+        eprintln!("{}", stats);
+    }
+    if let Err(err) = sink.write(&framebuffer) {
+        eprintln!("error: failed to write output: {}", err);
+        return ExitCode::FAILURE;
+    }
+    if let (Some(sink), Some(counts)) = (&mut sample_map, framebuffer.sample_count_image()) {
+        if let Err(err) = sink.write(&counts) {
+            eprintln!("error: failed to write sample map: {}", err);
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// The file sink for `--output`, with the format adjusted by `--png16` and
+/// `--jpeg-quality`.
+fn output_sink(path: &Path, options: &Options) -> Result<FileSink, String> {
+    let sink = FileSink::new(path).map_err(|err| err.to_string())?;
+    let mut format = sink.format();
+    if options.png16 {
+        format = format
+            .with_png16()
+            .ok_or_else(|| format!("{}: `--png16` needs a PNG output", path.display()))?;
+    }
+    if let Some(quality) = options.jpeg_quality {
+        format = format
+            .with_jpeg_quality(quality)
+            .ok_or_else(|| format!("{}: `--jpeg-quality` needs a JPEG output", path.display()))?;
+    }
+    Ok(FileSink::new_with_format(path, format))
+}
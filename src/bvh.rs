@@ -1,93 +1,444 @@
-use crate::{rand_i32, Aabb, HitRecord, Hittable, Interval, Ray};
-use std::rc::Rc;
-pub struct BVHNode {
-    left: Rc<dyn Hittable>,
-    right: Rc<dyn Hittable>,
-    bbox: Aabb,
+use crate::{Aabb, HitRecord, Hittable, Interval, Point3, Ray, Rng};
+use std::sync::Arc;
+
+/// Cost of visiting an inner node, relative to one primitive intersection.
+const TRAVERSAL_COST: f64 = 0.125;
+
+/// Deepest level at which the builder still looks for SAH splits. Below it,
+/// halving adds at most 32 more levels for any `u32` primitive count.
+const MAX_SAH_DEPTH: usize = 32;
+
+const STACK_SIZE: usize = MAX_SAH_DEPTH + 32;
+
+/// Shape of a built BVH, to compare trees.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BvhStats {
+    /// Nodes on the longest path from the root to a leaf, both included.
+    pub depth: usize,
+    pub nodes: usize,
+    pub leaves: usize,
+    /// Expected cost of tracing a ray through the tree, in primitive
+    /// intersections, as estimated by the surface area heuristic.
+    pub sah_cost: f64,
 }
 
-impl Hittable for BVHNode {
-    fn hit(&self, ray: &Ray, interval: &Interval) -> Option<HitRecord> {
-        if !self.bbox.hit(ray, interval) {
-            return None;
+/// Builds a `BVHNode` with the surface area heuristic, evaluated over
+/// primitive centroids sorted into `bins` buckets per axis.
+#[derive(Debug, Clone)]
+pub struct BvhBuilder {
+    max_leaf_size: usize,
+    bins: usize,
+}
+
+impl Default for BvhBuilder {
+    fn default() -> Self {
+        Self {
+            max_leaf_size: 4,
+            bins: 16,
+        }
+    }
+}
+
+impl BvhBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Most primitives a leaf may hold. Leaves stay smaller when the SAH
+    /// says splitting is cheaper.
+    pub fn with_max_leaf_size(mut self, max_leaf_size: usize) -> Self {
+        self.max_leaf_size = max_leaf_size.clamp(1, u16::MAX as usize);
+        self
+    }
+
+    pub fn with_bins(mut self, bins: usize) -> Self {
+        self.bins = bins.max(2);
+        self
+    }
+
+    pub fn build(&self, objects: Vec<Arc<dyn Hittable>>) -> BVHNode {
+        let boxes: Vec<Aabb> = objects.iter().map(|o| o.bounding_box()).collect();
+        BVHNode {
+            bvh: self.build_bvh(&boxes),
+            objects,
+        }
+    }
+
+    /// Builds a hierarchy over primitives with the given bounds, which the
+    /// leaves then refer to by their index in `boxes`.
+    pub(crate) fn build_bvh(&self, boxes: &[Aabb]) -> Bvh {
+        let prims: Vec<Prim> = boxes
+            .iter()
+            .enumerate()
+            .map(|(index, bbox)| Prim {
+                index: index as u32,
+                bbox: bbox.clone(),
+                centroid: bbox.centroid(),
+            })
+            .collect();
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(2 * prims.len()),
+            indices: Vec::with_capacity(prims.len()),
+            stats: BvhStats::default(),
+        };
+        if !prims.is_empty() {
+            self.build_node(&mut bvh, prims, 1);
         }
-        let hit_left = self.left.hit(ray, interval);
-        let max_time = if let Some(ref hit) = hit_left {
-            hit.t
+        let root_area = bvh.bounding_box().surface_area();
+        bvh.stats.sah_cost = if root_area > 0.0 {
+            bvh.stats.sah_cost / root_area
         } else {
-            interval.max
+            bvh.stats.leaves as f64
         };
-        let hit_right = self.right.hit(ray, &Interval::new(interval.min, max_time));
-        // let right first
-        if hit_right.is_some() {
-            hit_right
+        bvh
+    }
+
+    /// Appends the subtree over `prims` to `bvh.nodes` in depth-first order,
+    /// so a node's left child always comes right after it.
+    fn build_node(&self, bvh: &mut Bvh, prims: Vec<Prim>, depth: usize) {
+        let bbox = union(prims.iter().map(|p| &p.bbox)).unwrap_or_default();
+        let area = bbox.surface_area();
+        let stats = &mut bvh.stats;
+        stats.nodes += 1;
+        stats.depth = stats.depth.max(depth);
+
+        let n = prims.len();
+        // Past this depth the tree is degenerate, so stop trusting the SAH
+        // and halve: that keeps the depth within the traversal stack.
+        let split = if n > 1 && depth < MAX_SAH_DEPTH {
+            self.find_split(&prims, area)
         } else {
-            hit_left
+            None
+        };
+        let leaf_cost = n as f64 * area;
+        let (axis, left, right): (usize, Vec<_>, Vec<_>) = match split {
+            Some(split) if n > self.max_leaf_size || split.cost < leaf_cost => {
+                let (left, right) = prims.into_iter().partition(|p| split.goes_left(p));
+                (split.axis, left, right)
+            }
+            // Every centroid is in the same place, so no plane separates
+            // them. Halve the list anyway to honour the leaf size.
+            None if n > self.max_leaf_size => {
+                let mut left = prims;
+                let right = left.split_off(n / 2);
+                (0, left, right)
+            }
+            _ => {
+                stats.leaves += 1;
+                stats.sah_cost += leaf_cost;
+                bvh.nodes.push(LinearNode {
+                    bbox,
+                    offset: bvh.indices.len() as u32,
+                    count: n as u16,
+                    axis: 0,
+                });
+                bvh.indices.extend(prims.iter().map(|p| p.index));
+                return;
+            }
+        };
+        stats.sah_cost += TRAVERSAL_COST * area;
+        let index = bvh.nodes.len();
+        bvh.nodes.push(LinearNode {
+            bbox,
+            offset: 0,
+            count: 0,
+            axis: axis as u8,
+        });
+        self.build_node(bvh, left, depth + 1);
+        bvh.nodes[index].offset = bvh.nodes.len() as u32;
+        self.build_node(bvh, right, depth + 1);
+    }
+    /// The cheapest plane between two bins on any axis, if any plane has
+    /// primitives on both sides. `area` is the surface area of their bounds.
+    fn find_split(&self, prims: &[Prim], area: f64) -> Option<Split> {
+        let bins = self.bins;
+        let centroids = prims
+            .iter()
+            .map(|p| Aabb::new_from_points(p.centroid, p.centroid))
+            .reduce(Aabb::new_from_aabb)?;
+        let mut best: Option<Split> = None;
+        for (axis, extent) in centroids.axes().into_iter().enumerate() {
+            if extent.size() <= 0.0 {
+                continue;
+            }
+            let split = Split {
+                axis,
+                min: extent.min,
+                scale: bins as f64 / extent.size(),
+                bins,
+                bin: 0,
+                cost: 0.0,
+            };
+            let mut counts = vec![0usize; bins];
+            let mut boxes: Vec<Option<Aabb>> = vec![None; bins];
+            for p in prims {
+                let b = split.bin_of(p);
+                counts[b] += 1;
+                grow(&mut boxes[b], &p.bbox);
+            }
+
+            // Area and count of everything right of each boundary.
+            let mut right = vec![(0.0, 0); bins];
+            let mut acc: Option<Aabb> = None;
+            let mut count = 0;
+            for b in (1..bins).rev() {
+                if let Some(bbox) = &boxes[b] {
+                    grow(&mut acc, bbox);
+                }
+                count += counts[b];
+                right[b] = (acc.as_ref().map_or(0.0, Aabb::surface_area), count);
+            }
+            let mut acc: Option<Aabb> = None;
+            let mut count = 0;
+            for b in 1..bins {
+                if let Some(bbox) = &boxes[b - 1] {
+                    grow(&mut acc, bbox);
+                }
+                count += counts[b - 1];
+                let (right_area, right_count) = right[b];
+                if count == 0 || right_count == 0 {
+                    continue;
+                }
+                let left_area = acc.as_ref().map_or(0.0, Aabb::surface_area);
+                let cost = left_area * count as f64 + right_area * right_count as f64;
+                if best.as_ref().is_none_or(|s| cost < s.cost) {
+                    best = Some(Split {
+                        bin: b,
+                        cost,
+                        ..split
+                    });
+                }
+            }
         }
+        // Compare on the same footing as a leaf: bounds area times cost.
+        best.map(|s| Split {
+            cost: s.cost + TRAVERSAL_COST * area,
+            ..s
+        })
     }
-    fn bounding_box(&self) -> Aabb {
-        self.bbox.clone()
+}
+
+struct Prim {
+    index: u32,
+    bbox: Aabb,
+    centroid: Point3,
+}
+
+/// A plane at the lower edge of `bin` along `axis`.
+#[derive(Clone, Copy)]
+struct Split {
+    axis: usize,
+    min: f64,
+    scale: f64,
+    bins: usize,
+    bin: usize,
+    cost: f64,
+}
+
+impl Split {
+    fn bin_of(&self, prim: &Prim) -> usize {
+        let offset = (prim.centroid[self.axis] - self.min) * self.scale;
+        (offset as usize).min(self.bins - 1)
+    }
+
+    fn goes_left(&self, prim: &Prim) -> bool {
+        self.bin_of(prim) < self.bin
     }
 }
 
-impl BVHNode {
-    pub fn new(src_objects: Vec<Rc<dyn Hittable>>) -> Self {
-        let (left, right) = Self::generate(src_objects);
-        let bbox = Aabb::new_from_aabb(left.bounding_box(), right.bounding_box());
-        // dbg!(&bbox);
-        Self { left, right, bbox }
-    }
-
-    fn generate(src_objects: Vec<Rc<dyn Hittable>>) -> (Rc<dyn Hittable>, Rc<dyn Hittable>) {
-        let mut objects = src_objects;
-        let axis = rand_i32(0, 3);
-        let comparator = match axis {
-            0 => Self::box_x_compare,
-            1 => Self::box_y_compare,
-            _ => Self::box_z_compare,
-        };
-        let object_span = objects.len();
-        if object_span == 1 {
-            let node = objects[0].clone();
-            (node.clone(), node)
-        } else if object_span == 2 {
-            let node1: Rc<dyn Hittable> = objects[0].clone();
-            let node2: Rc<dyn Hittable> = objects[1].clone();
-            if comparator(&node1, &node2) {
-                (node1.clone(), node2)
-            } else {
-                (node2.clone(), node1)
-            }
-        } else {
-            objects.sort_by(|a, b| {
-                if comparator(a, b) {
-                    std::cmp::Ordering::Less
+fn union<'a>(boxes: impl Iterator<Item = &'a Aabb>) -> Option<Aabb> {
+    boxes.cloned().reduce(Aabb::new_from_aabb)
+}
+
+fn grow(acc: &mut Option<Aabb>, bbox: &Aabb) {
+    *acc = Some(match acc.take() {
+        Some(acc) => Aabb::new_from_aabb(acc, bbox.clone()),
+        None => bbox.clone(),
+    });
+}
+
+/// One node of a flattened BVH. A leaf (`count > 0`) covers
+/// `indices[offset..offset + count]`. An inner node has its left child right
+/// after it and its right child at `offset`, and `axis` is the axis it was
+/// split along.
+#[derive(Debug, Clone)]
+struct LinearNode {
+    bbox: Aabb,
+    offset: u32,
+    count: u16,
+    axis: u8,
+}
+
+/// A BVH stored as one array of nodes in depth-first order, over primitives
+/// that live elsewhere and are referred to by index.
+pub(crate) struct Bvh {
+    nodes: Vec<LinearNode>,
+    indices: Vec<u32>,
+    stats: BvhStats,
+}
+
+impl Bvh {
+    pub(crate) fn bounding_box(&self) -> Aabb {
+        self.nodes
+            .first()
+            .map(|n| n.bbox.clone())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn stats(&self) -> BvhStats {
+        self.stats
+    }
+
+    /// Walks the nodes the ray passes through, nearer child first, and calls
+    /// `hit` on the index of each primitive in them. `hit` returns the
+    /// distance of an intersection within the interval it is given, which
+    /// then shrinks to that distance.
+    pub(crate) fn traverse(
+        &self,
+        ray: &Ray,
+        interval: &Interval,
+        mut hit: impl FnMut(usize, &Interval) -> Option<f64>,
+    ) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let dir = ray.dir();
+        let dir_is_neg = [dir.x() < 0.0, dir.y() < 0.0, dir.z() < 0.0];
+        let mut closest = interval.max;
+        let mut stack = [0u32; STACK_SIZE];
+        let mut top = 0;
+        let mut current = 0;
+        loop {
+            let node = &self.nodes[current];
+            if node.bbox.hit(ray, &Interval::new(interval.min, closest)) {
+                if node.count > 0 {
+                    let start = node.offset as usize;
+                    for &index in &self.indices[start..start + node.count as usize] {
+                        if let Some(t) = hit(index as usize, &Interval::new(interval.min, closest))
+                        {
+                            closest = t;
+                        }
+                    }
                 } else {
-                    std::cmp::Ordering::Greater
+                    let (near, far) = if dir_is_neg[node.axis as usize] {
+                        (node.offset, current as u32 + 1)
+                    } else {
+                        (current as u32 + 1, node.offset)
+                    };
+                    stack[top] = far;
+                    top += 1;
+                    current = near as usize;
+                    continue;
                 }
-            });
-            let mid = object_span / 2;
-            let left = BVHNode::new(objects.drain(0..mid).collect());
-            let right = BVHNode::new(objects);
-            // (Rc::from(left), Rc::from(right))
-            // unimplemented!()
-            (Rc::from(left), Rc::from(right))
+            }
+            if top == 0 {
+                break;
+            }
+            top -= 1;
+            current = stack[top] as usize;
         }
     }
+}
+
+/// A bounding volume hierarchy over a set of objects.
+pub struct BVHNode {
+    objects: Vec<Arc<dyn Hittable>>,
+    bvh: Bvh,
+}
 
-    fn box_compare(a: &Rc<dyn Hittable>, b: &Rc<dyn Hittable>, axis: usize) -> bool {
-        let box_a = a.bounding_box();
-        let box_b = b.bounding_box();
-        box_a.axis(axis).min < box_b.axis(axis).min
+impl Hittable for BVHNode {
+    fn hit(&self, ray: &Ray, interval: &Interval, rng: &mut dyn Rng) -> Option<HitRecord> {
+        let mut result: Option<HitRecord> = None;
+        self.bvh.traverse(ray, interval, |index, interval| {
+            let record = self.objects[index].hit(ray, interval, rng)?;
+            let t = record.t;
+            result = Some(record);
+            Some(t)
+        });
+        result
+    }
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
     }
+}
 
-    fn box_x_compare(a: &Rc<dyn Hittable>, b: &Rc<dyn Hittable>) -> bool {
-        Self::box_compare(a, b, 0)
+impl BVHNode {
+    /// Builds with the default `BvhBuilder` settings.
+    pub fn new(objects: Vec<Arc<dyn Hittable>>) -> Self {
+        BvhBuilder::default().build(objects)
     }
-    fn box_y_compare(a: &Rc<dyn Hittable>, b: &Rc<dyn Hittable>) -> bool {
-        Self::box_compare(a, b, 1)
+
+    pub fn stats(&self) -> BvhStats {
+        self.bvh.stats()
     }
-    fn box_z_compare(a: &Rc<dyn Hittable>, b: &Rc<dyn Hittable>) -> bool {
-        Self::box_compare(a, b, 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{BVHNode, BvhBuilder};
+    use crate::{
+        Color, Hittable, HittableList, Interval, Lambertian, Pcg32, Point3, Ray, Rng, Sphere, Vec3,
+    };
+
+    fn spheres(rng: &mut dyn Rng) -> Vec<Arc<dyn Hittable>> {
+        let mat = Arc::new(Lambertian::new_from_color(Color::new(0.5, 0.5, 0.5)));
+        (0..300)
+            .map(|_| {
+                let center = Point3::random_range(rng, -10.0, 10.0);
+                Arc::new(Sphere::new(center, rng.range(0.1, 1.0), mat.clone())) as _
+            })
+            .collect()
+    }
+
+    #[test]
+    fn leaf_size_and_node_counts() {
+        let mut rng = Pcg32::new(1, 0);
+        let objects = spheres(&mut rng);
+        for leaf in [1, 4] {
+            let bvh = BvhBuilder::new()
+                .with_max_leaf_size(leaf)
+                .build(objects.clone());
+            let stats = bvh.stats();
+            assert_eq!(stats.nodes, 2 * stats.leaves - 1);
+            assert!(stats.leaves >= objects.len() / leaf);
+            assert!(stats.depth < 30, "{:?}", stats);
+            assert!(stats.sah_cost > 0.0);
+        }
+        let single = BVHNode::new(objects[..1].to_vec()).stats();
+        assert_eq!((single.nodes, single.leaves, single.depth), (1, 1, 1));
+
+        // Coincident centroids leave no plane to split at, but leaves must
+        // still respect their size.
+        let stacked = BvhBuilder::new()
+            .with_max_leaf_size(2)
+            .build(vec![objects[0].clone(); 9])
+            .stats();
+        assert_eq!(stacked.leaves, 5);
+        assert!(BVHNode::new(Vec::new())
+            .hit(
+                &Ray::new(Point3::default(), Vec3::new(0.0, 0.0, 1.0)),
+                &Interval::universe(),
+                &mut rng
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn same_hits_as_a_list() {
+        let mut rng = Pcg32::new(2, 0);
+        let objects = spheres(&mut rng);
+        let bvh = BVHNode::new(objects.clone());
+        let mut list = HittableList::new();
+        objects.into_iter().for_each(|o| list.add(o));
+        for _ in 0..500 {
+            let origin = Point3::random_range(&mut rng, -15.0, 15.0);
+            let ray = Ray::new(origin, Vec3::random_unit_vector(&mut rng));
+            let interval = Interval::new(0.001, f64::INFINITY);
+            let a = bvh.hit(&ray, &interval, &mut rng).map(|r| r.t);
+            let b = list.hit(&ray, &interval, &mut rng).map(|r| r.t);
+            assert_eq!(a, b);
+        }
     }
 }
@@ -1,10 +1,123 @@
 #![allow(dead_code)]
 #![allow(unused_imports)]
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use serde::Deserialize;
+
 use crate::{
-    deg2rad, rand, ray, write_color, Color, Hittable, HittableList, Interval, Point3, Ray, Vec3,
-    INF,
+    deg2rad, Albedo, AmbientOcclusion, Color, Depth, Framebuffer, HittableList, Integrator,
+    IntegratorKind, LightSampling, Normals, PathTracer, Point3, Ray, Sampler, SamplerKind, Vec3,
+    Whitted, PI,
 };
 
+/// Everything needed to build a `Camera`, in a form that can be tweaked
+/// (e.g. from the command line) or read from a scene file first.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraConfig {
+    pub aspect_ratio: f64,
+    pub image_width: usize,
+    pub lookfrom: Point3,
+    pub lookat: Point3,
+    pub vup: Vec3,
+    pub sample_per_pixel: usize,
+    pub max_depth: usize,
+    pub fov: f64,
+    pub defocus_angle: f64,
+    pub focus_dist: f64,
+    pub background: Color,
+    pub integrator: IntegratorKind,
+    pub sampler: SamplerKind,
+    /// Stops sampling a pixel once its noise drops below this, see
+    /// `Camera::with_adaptive_sampling`. `sample_per_pixel` stays the cap.
+    pub noise_threshold: Option<f64>,
+    /// Samples every pixel takes before adaptive sampling may stop it.
+    pub min_sample_per_pixel: usize,
+    /// Occlusion radius for `IntegratorKind::AmbientOcclusion`; a tenth of
+    /// the scene's size when unset.
+    pub ao_radius: Option<f64>,
+    pub light_sampling: LightSampling,
+    /// Bounces after which Russian roulette may end a path. Set it to
+    /// `max_depth` or more to turn roulette off.
+    pub roulette_min_depth: usize,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self {
+            aspect_ratio: 1.0,
+            image_width: 400,
+            lookfrom: Point3::new(0.0, 0.0, 0.0),
+            lookat: Point3::new(0.0, 0.0, -1.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            sample_per_pixel: 100,
+            max_depth: 50,
+            fov: 90.0,
+            defocus_angle: 0.0,
+            focus_dist: 10.0,
+            background: Color::new(0.0, 0.0, 0.0),
+            integrator: IntegratorKind::default(),
+            sampler: SamplerKind::default(),
+            noise_threshold: None,
+            min_sample_per_pixel: 16,
+            ao_radius: None,
+            light_sampling: LightSampling::default(),
+            roulette_min_depth: 5,
+        }
+    }
+}
+
+impl CameraConfig {
+    pub fn build(&self) -> Camera {
+        let cam = Camera::new(
+            self.aspect_ratio,
+            self.image_width,
+            self.lookfrom,
+            self.lookat,
+            self.vup,
+            self.sample_per_pixel,
+            self.max_depth,
+            self.fov,
+            self.defocus_angle,
+            self.focus_dist,
+            self.background,
+        )
+        .with_integrator(self.integrator())
+        .with_sampler(self.sampler);
+        match self.noise_threshold {
+            Some(threshold) => cam.with_adaptive_sampling(threshold, self.min_sample_per_pixel),
+            None => cam,
+        }
+    }
+
+    /// The path tracer these settings describe, whichever integrator is
+    /// picked.
+    pub fn path_tracer(&self) -> PathTracer {
+        PathTracer::new(self.max_depth, self.background)
+            .with_light_sampling(self.light_sampling)
+            .with_roulette_min_depth(self.roulette_min_depth)
+    }
+
+    pub fn integrator(&self) -> Arc<dyn Integrator> {
+        match self.integrator {
+            IntegratorKind::Path => Arc::new(self.path_tracer()),
+            IntegratorKind::AmbientOcclusion => {
+                let ao = AmbientOcclusion::new();
+                Arc::new(match self.ao_radius {
+                    Some(radius) => ao.with_radius(radius),
+                    None => ao,
+                })
+            }
+            IntegratorKind::Normals => Arc::new(Normals),
+            IntegratorKind::Albedo => Arc::new(Albedo::new(self.background)),
+            IntegratorKind::Depth => Arc::new(Depth::new()),
+            IntegratorKind::Whitted => Arc::new(Whitted::new(self.max_depth, self.background)),
+        }
+    }
+}
+
 pub struct Camera {
     image_width: usize,
     image_height: usize,
@@ -26,9 +139,16 @@ pub struct Camera {
     defocus_disk_u: Vec3,
     defocus_disk_v: Vec3,
     background: Color,
+    threads: usize,
+    seed: u64,
+    integrator: Arc<dyn Integrator>,
+    sampler: SamplerKind,
+    noise_threshold: Option<f64>,
+    min_sample_per_pixel: usize,
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         aspect_ratio: f64,
         image_width: usize,
@@ -85,68 +205,275 @@ impl Camera {
             defocus_disk_u,
             defocus_disk_v,
             background,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            seed: 0,
+            integrator: Arc::new(PathTracer::new(max_depth, background)),
+            sampler: SamplerKind::default(),
+            noise_threshold: None,
+            min_sample_per_pixel: 1,
         }
     }
 
-    pub fn render(&self, world: &HittableList) {
-        println!("P3");
-        println!("{} {}", self.image_width, self.image_height);
-        println!("255");
+    /// Sets the number of worker threads used by `render`, at least one.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
 
-        for i in 0..self.image_height {
-            eprint!("Lines remaining: {:>5}", self.image_height - i);
-            eprint!("\r");
-            for j in 0..self.image_width {
-                let mut final_color = Color::new(0.0, 0.0, 0.0);
-                for _ in 0..self.sample_per_pixel {
-                    let r = self.get_ray(i, j);
-                    let color = self.ray_color(&r, self.max_depth, world);
-                    final_color += color;
+    /// Sets the seed every pixel's random stream is derived from. The same
+    /// seed always produces the same image, whatever the thread count.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Replaces the default path tracer, built from `max_depth` and
+    /// `background`, with another integrator.
+    pub fn with_integrator(mut self, integrator: Arc<dyn Integrator>) -> Self {
+        self.integrator = integrator;
+        self
+    }
+
+    /// Picks the sample values for pixel positions, lens points, times and
+    /// everything the integrator samples.
+    pub fn with_sampler(mut self, sampler: SamplerKind) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Lets each pixel stop before `sample_per_pixel` samples, but not before
+    /// `min_sample_per_pixel`, once the standard error of its mean
+    /// luminance, measured after gamma encoding, is below `noise_threshold`
+    /// (e.g. `0.01` is about 2.5 of 255 levels).
+    pub fn with_adaptive_sampling(
+        mut self,
+        noise_threshold: f64,
+        min_sample_per_pixel: usize,
+    ) -> Self {
+        self.noise_threshold = Some(noise_threshold);
+        self.min_sample_per_pixel = min_sample_per_pixel.max(2);
+        self
+    }
+
+    /// Traces the scene with the camera's integrator and returns the
+    /// averaged, linear pixel colors, along with the samples each pixel
+    /// took. `lights` may be empty.
+    pub fn render(&self, world: &HittableList, lights: &HittableList) -> Framebuffer {
+        let (pixels, sample_counts) = self
+            .render_rows(world, lights)
+            .into_iter()
+            .flatten()
+            .unzip();
+        Framebuffer::new_from_pixels(self.image_width, self.image_height, pixels)
+            .with_sample_counts(sample_counts)
+    }
+
+    /// Renders every scanline, handing rows out to the worker threads one at a
+    /// time. The result is ordered top to bottom regardless of which thread
+    /// traced a row.
+    fn render_rows(&self, world: &HittableList, lights: &HittableList) -> Vec<Vec<(Color, usize)>> {
+        let next_row = AtomicUsize::new(0);
+        let remaining = AtomicUsize::new(self.image_height);
+        let threads = self.threads.min(self.image_height);
+
+        let mut rows = vec![Vec::new(); self.image_height];
+        thread::scope(|s| {
+            let workers: Vec<_> = (0..threads)
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut done = Vec::new();
+                        loop {
+                            let i = next_row.fetch_add(1, Ordering::Relaxed);
+                            if i >= self.image_height {
+                                break done;
+                            }
+                            done.push((i, self.render_row(i, world, lights)));
+                            let left = remaining.fetch_sub(1, Ordering::Relaxed) - 1;
+                            eprint!("Lines remaining: {:>5}", left);
+                            eprint!("\r");
+                        }
+                    })
+                })
+                .collect();
+            for worker in workers {
+                for (i, row) in worker.join().expect("render thread panicked") {
+                    rows[i] = row;
                 }
-                write_color(&final_color, self.sample_per_pixel);
             }
-        }
+        });
         eprintln!("DONE");
+        rows
+    }
+
+    /// The average color of every pixel in row `i` and how many samples it
+    /// took.
+    fn render_row(
+        &self,
+        i: usize,
+        world: &HittableList,
+        lights: &HittableList,
+    ) -> Vec<(Color, usize)> {
+        let mut sampler = self.sampler.build(self.seed, self.sample_per_pixel);
+        (0..self.image_width)
+            .map(|j| {
+                let mut final_color = Color::new(0.0, 0.0, 0.0);
+                // Running mean and sum of squared deviations of the
+                // luminance (Welford).
+                let (mut mean, mut m2) = (0.0, 0.0);
+                let mut samples = 0;
+                while samples < self.sample_per_pixel {
+                    sampler.start_pixel_sample(j, i, samples);
+                    let r = self.get_ray(i, j, sampler.as_mut());
+                    let color = self
+                        .integrator
+                        .ray_color(&r, world, lights, sampler.as_mut());
+                    final_color += color;
+                    samples += 1;
+
+                    let Some(threshold) = self.noise_threshold else {
+                        continue;
+                    };
+                    let luminance = 0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z();
+                    let delta = luminance - mean;
+                    mean += delta / samples as f64;
+                    m2 += delta * (luminance - mean);
+                    if samples >= self.min_sample_per_pixel {
+                        // Gamma encoding takes a square root, which scales
+                        // errors by 1 / (2 sqrt(mean)).
+                        let std_error = (m2 / ((samples - 1) * samples) as f64).sqrt();
+                        if std_error / (2.0 * mean.max(1e-4).sqrt()) < threshold {
+                            break;
+                        }
+                    }
+                }
+                (final_color / samples as f64, samples)
+            })
+            .collect()
     }
 
-    fn get_ray(&self, i: usize, j: usize) -> Ray {
+    /// A ray through pixel `(i, j)`, drawing the pixel offset, lens point
+    /// and time from the first five dimensions of `sampler`.
+    fn get_ray(&self, i: usize, j: usize, sampler: &mut dyn Sampler) -> Ray {
         let pixel_center =
             self.pixel00 + j as f64 * self.pixel_delta_u + i as f64 * self.pixel_delta_v;
-        let pixel_sample = pixel_center + self.sample_square();
+        let (px, py) = sampler.get_2d();
+        let pixel_sample =
+            pixel_center + (px - 0.5) * self.pixel_delta_u + (py - 0.5) * self.pixel_delta_v;
+        // The lens dimensions are drawn even without defocus so that later
+        // dimensions line up the same way in every camera.
+        let lens = sampler.get_2d();
         let ray_origin = if self.defocus_angle <= 0.0 {
             self.center
         } else {
-            self.defocus_disk_sample()
+            self.defocus_disk_sample(lens)
         };
         let ray_dir = pixel_sample - ray_origin;
-        let ray_time = rand();
+        let ray_time = sampler.get_1d();
         Ray::new_with_time(ray_origin, ray_dir, ray_time)
     }
 
-    fn defocus_disk_sample(&self) -> Point3 {
-        let p = Vec3::random_in_unit_disk();
-        self.center + p[0] * self.defocus_disk_u + p[1] * self.defocus_disk_v
+    /// Maps a point of the unit square onto the lens with Shirley's
+    /// concentric mapping, which keeps strata from the sampler intact.
+    fn defocus_disk_sample(&self, (u, v): (f64, f64)) -> Point3 {
+        let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+        let (r, theta) = if a == 0.0 && b == 0.0 {
+            (0.0, 0.0)
+        } else if a.abs() > b.abs() {
+            (a, PI / 4.0 * (b / a))
+        } else {
+            (b, PI / 2.0 - PI / 4.0 * (a / b))
+        };
+        self.center + r * theta.cos() * self.defocus_disk_u + r * theta.sin() * self.defocus_disk_v
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
 
-    fn sample_square(&self) -> Vec3 {
-        (rand() - 0.5) * self.pixel_delta_u + (rand() - 0.5) * self.pixel_delta_v
+    use super::Camera;
+    use crate::{
+        BVHNode, Color, ConstantMedium, Dielectric, HittableList, Lambertian, NoiseTexture, Pcg32,
+        Point3, SamplerKind, Sphere, Vec3,
+    };
+
+    fn scene() -> (HittableList, Camera) {
+        let mut rng = Pcg32::new(3, 0);
+        let mut world = HittableList::new();
+        let noise = Arc::new(Lambertian::new(Box::new(NoiseTexture::new(4.0, &mut rng))));
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0.0, -100.5, -1.0),
+            100.0,
+            noise,
+        )));
+        let glass = Arc::new(Sphere::new(
+            Point3::new(0.0, 0.0, -1.0),
+            0.5,
+            Arc::new(Dielectric::new(1.5)),
+        ));
+        world.add(glass.clone());
+        world.add(Arc::new(ConstantMedium::new_from_color(
+            glass,
+            2.0,
+            Color::new(0.2, 0.4, 0.9),
+        )));
+        let world = HittableList::new_from_node(Arc::new(BVHNode::new(world.into())));
+        let cam = Camera::new(
+            1.0,
+            12,
+            Point3::new(0.0, 0.0, 1.0),
+            Point3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            4,
+            8,
+            60.0,
+            1.0,
+            2.0,
+            Color::new(0.7, 0.8, 1.0),
+        );
+        (world, cam)
     }
 
-    fn ray_color(&self, ray: &Ray, depth: usize, world: &HittableList) -> Color {
-        if depth == 0 {
-            return Color::new(0.0, 0.0, 0.0);
-        }
-        if let Some(record) = world.hit(ray, &Interval::new(0.001, INF)) {
-            let color_from_emission = record.mat.emiited(record.u, record.v, &record.point);
-            if let Some((attenuation, scattered)) = record.mat.scatter(ray, &record) {
-                color_from_emission + attenuation * self.ray_color(&scattered, depth - 1, world)
-            } else {
-                color_from_emission
-            }
-            // let direction = record.normal + Vec3::random_unit_vector();
-            // 0.5 * self.ray_color(&Ray::new(record.point, direction), depth - 1, world)
-        } else {
-            self.background
+    #[test]
+    fn seeded_render_is_independent_of_thread_count() {
+        for sampler in [SamplerKind::Independent, SamplerKind::Sobol] {
+            let (world, cam) = scene();
+            let cam = cam.with_seed(7).with_sampler(sampler);
+            let single = cam.with_threads(1).render(&world, &HittableList::new());
+            let (world, cam) = scene();
+            let multi = cam
+                .with_seed(7)
+                .with_sampler(sampler)
+                .with_threads(5)
+                .render(&world, &HittableList::new());
+            assert_eq!(single.pixels(), multi.pixels(), "{:?}", sampler);
         }
     }
+
+    #[test]
+    fn adaptive_sampling_stops_where_converged() {
+        let (world, cam) = scene();
+        let fb = cam
+            .with_threads(1)
+            .with_adaptive_sampling(0.02, 2)
+            .render(&world, &HittableList::new());
+        let counts = fb.sample_counts().unwrap();
+        assert!(counts.iter().all(|&n| (2..=4).contains(&n)));
+        // The flat sky converges at once, the noisy ground and glass don't.
+        assert_eq!(counts[0], 2);
+        assert!(counts.contains(&4));
+
+        let (world, cam) = scene();
+        let fixed = cam.render(&world, &HittableList::new());
+        assert!(fixed.sample_counts().unwrap().iter().all(|&n| n == 4));
+    }
+
+    #[test]
+    fn different_seeds_differ() {
+        let (world, cam) = scene();
+        let a = cam.with_seed(1).render(&world, &HittableList::new());
+        let (world, cam) = scene();
+        let b = cam.with_seed(2).render(&world, &HittableList::new());
+        assert_ne!(a.pixels(), b.pixels());
+    }
 }
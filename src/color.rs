@@ -1,4 +1,6 @@
-use crate::vec3::Vec3;
+use std::io::{self, Write};
+
+use crate::{vec3::Vec3, Interval};
 
 pub type Color = Vec3;
 
@@ -6,18 +8,29 @@ fn linear_to_gamma(linear: f64) -> f64 {
     linear.sqrt()
 }
 
-pub fn write_color(pixel_color: &Color, sample_per_pixel: usize) {
-    let r = pixel_color.x() / sample_per_pixel as f64;
-    let g = pixel_color.y() / sample_per_pixel as f64;
-    let b = pixel_color.z() / sample_per_pixel as f64;
-    let r = linear_to_gamma(r);
-    let g = linear_to_gamma(g);
-    let b = linear_to_gamma(b);
+/// Gamma-encodes a linear color and quantizes it to 8 bits per channel.
+pub fn color_to_rgb8(pixel_color: &Color) -> [u8; 3] {
+    let intensity = Interval::new(0.0, 0.999);
+    let encode = |c: f64| (256.0 * intensity.clamp(linear_to_gamma(c.max(0.0)))) as u8;
+    [
+        encode(pixel_color.x()),
+        encode(pixel_color.y()),
+        encode(pixel_color.z()),
+    ]
+}
+
+/// Gamma-encodes a linear color and quantizes it to 16 bits per channel.
+pub fn color_to_rgb16(pixel_color: &Color) -> [u16; 3] {
+    let intensity = Interval::new(0.0, 1.0);
+    let encode = |c: f64| (65535.0 * intensity.clamp(linear_to_gamma(c.max(0.0)))).round() as u16;
+    [
+        encode(pixel_color.x()),
+        encode(pixel_color.y()),
+        encode(pixel_color.z()),
+    ]
+}
 
-    println!(
-        "{} {} {}",
-        (r * 255.0) as usize,
-        (g * 255.0) as usize,
-        (b * 255.0) as usize
-    );
+pub fn write_color(out: &mut impl Write, pixel_color: &Color) -> io::Result<()> {
+    let [r, g, b] = color_to_rgb8(pixel_color);
+    writeln!(out, "{} {} {}", r, g, b)
 }
@@ -0,0 +1,86 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Everything that can go wrong while loading scenes and assets or writing
+/// renders. File-related variants carry the path so the message can name it.
+#[derive(Debug)]
+pub enum Error {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// Writing to a stream that has no path, e.g. stdout.
+    Stream(io::Error),
+    Image {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    Exr {
+        path: PathBuf,
+        source: exr::error::Error,
+    },
+    UnsupportedFormat(PathBuf),
+    SceneSyntax {
+        path: Option<PathBuf>,
+        source: toml::de::Error,
+    },
+    ObjSyntax {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    UnknownTexture(String),
+    UnknownMaterial(String),
+    TextureCycle(String),
+    InvalidAxis(usize),
+    InvalidLayers(&'static str),
+    InvalidMaterial(&'static str),
+    InvalidLight(&'static str),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Stream(source) => write!(f, "{}", source),
+            Error::Image { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Exr { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::UnsupportedFormat(path) => {
+                write!(f, "{}: unsupported image format", path.display())
+            }
+            Error::SceneSyntax {
+                path: Some(path),
+                source,
+            } => write!(f, "{}: {}", path.display(), source),
+            Error::SceneSyntax { path: None, source } => write!(f, "{}", source),
+            Error::ObjSyntax {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            Error::UnknownTexture(name) => write!(f, "unknown texture `{}`", name),
+            Error::UnknownMaterial(name) => write!(f, "unknown material `{}`", name),
+            Error::TextureCycle(name) => write!(f, "texture `{}` refers back to itself", name),
+            Error::InvalidAxis(axis) => write!(f, "invalid axis {}, expected 0, 1 or 2", axis),
+            Error::InvalidLayers(reason) => write!(f, "invalid EXR layers: {}", reason),
+            Error::InvalidMaterial(reason) => write!(f, "invalid material: {}", reason),
+            Error::InvalidLight(reason) => write!(f, "invalid light: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Stream(source) => Some(source),
+            Error::Image { source, .. } => Some(source),
+            Error::Exr { source, .. } => Some(source),
+            Error::SceneSyntax { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
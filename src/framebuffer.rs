@@ -0,0 +1,103 @@
+use crate::Color;
+
+/// Linear RGB pixels stored row by row, starting at the top-left corner.
+#[derive(Debug, Clone)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+    sample_counts: Option<Vec<usize>>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color::zero(); width * height],
+            sample_counts: None,
+        }
+    }
+
+    pub fn new_from_pixels(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert_eq!(
+            pixels.len(),
+            width * height,
+            "pixel count does not match {}x{}",
+            width,
+            height
+        );
+        Self {
+            width,
+            height,
+            pixels,
+            sample_counts: None,
+        }
+    }
+
+    /// Attaches how many samples each pixel took, in the same order as the
+    /// pixels.
+    pub fn with_sample_counts(mut self, sample_counts: Vec<usize>) -> Self {
+        assert_eq!(
+            sample_counts.len(),
+            self.pixels.len(),
+            "sample count does not match {}x{}",
+            self.width,
+            self.height
+        );
+        self.sample_counts = Some(sample_counts);
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        self.pixels[x + y * self.width]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Color) {
+        self.pixels[x + y * self.width] = color;
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Color] {
+        &mut self.pixels
+    }
+
+    pub fn sample_counts(&self) -> Option<&[usize]> {
+        self.sample_counts.as_deref()
+    }
+
+    /// The sample counts as a gray image, linear in the count and white
+    /// where it is largest.
+    pub fn sample_count_image(&self) -> Option<Framebuffer> {
+        let counts = self.sample_counts()?;
+        let max = counts.iter().copied().max().unwrap_or(0).max(1) as f64;
+        let pixels = counts
+            .iter()
+            .map(|&n| {
+                let v = n as f64 / max;
+                Color::new(v, v, v)
+            })
+            .collect();
+        Some(Self::new_from_pixels(self.width, self.height, pixels))
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[Color]> {
+        self.pixels.chunks(self.width.max(1))
+    }
+
+    /// Applies `f` to every pixel, e.g. for exposure or tone mapping.
+    pub fn map(&mut self, f: impl Fn(Color) -> Color) {
+        self.pixels.iter_mut().for_each(|p| *p = f(*p));
+    }
+}
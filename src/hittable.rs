@@ -1,15 +1,21 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
-use crate::{rand, Aabb, Color, Interval, Isotropic, Material, Point3, Ray, Texture, Vec3};
+use crate::{Aabb, Color, Interval, Isotropic, Material, Point3, Ray, Rng, Texture, Vec3};
 
+#[derive(Clone)]
 pub struct HitRecord {
     pub point: Point3,
     pub normal: Vec3,
     pub t: f64,
     pub front_face: bool,
-    pub mat: Rc<dyn Material>,
+    pub mat: Arc<dyn Material>,
     pub u: f64,
     pub v: f64,
+    /// How the point moves with `u`, i.e. dp/du. Not normalized, and zero
+    /// when the shape has no parameterization.
+    pub tangent: Vec3,
+    /// How the point moves with `v`, i.e. dp/dv.
+    pub bitangent: Vec3,
 }
 
 impl HitRecord {
@@ -18,7 +24,7 @@ impl HitRecord {
         point: Point3,
         normal: Vec3,
         t: f64,
-        mat: Rc<dyn Material>,
+        mat: Arc<dyn Material>,
         u: f64,
         v: f64,
     ) -> Self {
@@ -31,60 +37,79 @@ impl HitRecord {
             mat,
             u,
             v,
+            tangent: Vec3::zero(),
+            bitangent: Vec3::zero(),
         }
     }
+
+    pub fn with_tangents(mut self, tangent: Vec3, bitangent: Vec3) -> Self {
+        self.tangent = tangent;
+        self.bitangent = bitangent;
+        self
+    }
 }
 
-pub trait Hittable {
-    fn hit(&self, ray: &Ray, interval: &Interval) -> Option<HitRecord>;
+pub trait Hittable: Send + Sync {
+    fn hit(&self, ray: &Ray, interval: &Interval, rng: &mut dyn Rng) -> Option<HitRecord>;
     fn bounding_box(&self) -> Aabb;
+
+    /// Density, over directions from `origin`, with which `random` picks
+    /// `direction`. Zero for objects that can't be sampled as lights.
+    fn pdf_value(&self, _origin: &Point3, _direction: &Vec3, _rng: &mut dyn Rng) -> f64 {
+        0.0
+    }
+
+    /// A direction from `origin` towards a random point on the object.
+    fn random(&self, _origin: &Point3, _rng: &mut dyn Rng) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
 }
 
 pub struct Translate {
     offset: Vec3,
-    obj: Rc<dyn Hittable>,
+    obj: Arc<dyn Hittable>,
     bbox: Aabb,
 }
 
 impl Translate {
-    pub fn new(obj: Rc<dyn Hittable>, offset: Vec3) -> Self {
+    pub fn new(obj: Arc<dyn Hittable>, offset: Vec3) -> Self {
         let bbox = obj.bounding_box() + offset;
         Self { obj, offset, bbox }
     }
 }
 
 impl Hittable for Translate {
-    fn hit(&self, ray: &Ray, interval: &Interval) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, interval: &Interval, rng: &mut dyn Rng) -> Option<HitRecord> {
         let offset_r = Ray::new_with_time(ray.origin() - self.offset, ray.dir(), ray.time());
-        if let Some(record) = self.obj.hit(&offset_r, interval) {
-            Some(HitRecord::new(
-                ray,
-                record.point + self.offset,
-                record.normal,
-                record.t,
-                record.mat,
-                record.u,
-                record.v,
-            ))
-        } else {
-            None
-        }
+        // Moving the hit keeps its side, which the child worked out from
+        // its outward normal.
+        let mut record = self.obj.hit(&offset_r, interval, rng)?;
+        record.point += self.offset;
+        Some(record)
     }
 
     fn bounding_box(&self) -> Aabb {
         self.bbox.clone()
     }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, rng: &mut dyn Rng) -> f64 {
+        self.obj.pdf_value(&(*origin - self.offset), direction, rng)
+    }
+
+    fn random(&self, origin: &Point3, rng: &mut dyn Rng) -> Vec3 {
+        self.obj.random(&(*origin - self.offset), rng)
+    }
 }
 
 pub struct RotateY {
-    obj: Rc<dyn Hittable>,
+    obj: Arc<dyn Hittable>,
     sin_theta: f64,
     cos_theta: f64,
     bbox: Aabb,
 }
 
 impl RotateY {
-    pub fn new(obj: Rc<dyn Hittable>, angle: f64) -> Self {
+    pub fn new(obj: Arc<dyn Hittable>, angle: f64) -> Self {
         let radians = angle.to_radians();
         let sin_theta = radians.sin();
         let cos_theta = radians.cos();
@@ -122,78 +147,83 @@ impl RotateY {
     }
 }
 
-impl Hittable for RotateY {
-    fn hit(&self, ray: &Ray, interval: &Interval) -> Option<HitRecord> {
-        let origin = ray.origin();
-        let dir = ray.dir();
-        let origin = Vec3::new(
-            self.cos_theta * origin.x() - self.sin_theta * origin.z(),
-            origin.y(),
-            self.sin_theta * origin.x() + self.cos_theta * origin.z(),
-        );
-        let dir = Vec3::new(
-            self.cos_theta * dir.x() - self.sin_theta * dir.z(),
-            dir.y(),
-            self.sin_theta * dir.x() + self.cos_theta * dir.z(),
-        );
-
-        let rotated_r = Ray::new_with_time(origin, dir, ray.time());
+impl RotateY {
+    fn to_object(&self, v: Vec3) -> Vec3 {
+        Vec3::new(
+            self.cos_theta * v.x() - self.sin_theta * v.z(),
+            v.y(),
+            self.sin_theta * v.x() + self.cos_theta * v.z(),
+        )
+    }
 
-        if let Some(record) = self.obj.hit(&rotated_r, interval) {
-            let point = Vec3::new(
-                self.cos_theta * record.point.x() + self.sin_theta * record.point.z(),
-                record.point.y(),
-                -self.sin_theta * record.point.x() + self.cos_theta * record.point.z(),
-            );
+    fn to_world(&self, v: Vec3) -> Vec3 {
+        Vec3::new(
+            self.cos_theta * v.x() + self.sin_theta * v.z(),
+            v.y(),
+            -self.sin_theta * v.x() + self.cos_theta * v.z(),
+        )
+    }
+}
 
-            let normal = Vec3::new(
-                self.cos_theta * record.normal.x() + self.sin_theta * record.normal.z(),
-                record.normal.y(),
-                -self.sin_theta * record.normal.x() + self.cos_theta * record.normal.z(),
-            );
+impl Hittable for RotateY {
+    fn hit(&self, ray: &Ray, interval: &Interval, rng: &mut dyn Rng) -> Option<HitRecord> {
+        let rotated_r = Ray::new_with_time(
+            self.to_object(ray.origin()),
+            self.to_object(ray.dir()),
+            ray.time(),
+        );
 
-            Some(HitRecord::new(
-                ray, point, normal, record.t, record.mat, record.u, record.v,
-            ))
-        } else {
-            None
-        }
+        let mut record = self.obj.hit(&rotated_r, interval, rng)?;
+        record.point = self.to_world(record.point);
+        record.normal = self.to_world(record.normal);
+        record.tangent = self.to_world(record.tangent);
+        record.bitangent = self.to_world(record.bitangent);
+        Some(record)
     }
 
     fn bounding_box(&self) -> Aabb {
         self.bbox.clone()
     }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, rng: &mut dyn Rng) -> f64 {
+        self.obj
+            .pdf_value(&self.to_object(*origin), &self.to_object(*direction), rng)
+    }
+
+    fn random(&self, origin: &Point3, rng: &mut dyn Rng) -> Vec3 {
+        self.to_world(self.obj.random(&self.to_object(*origin), rng))
+    }
 }
 
 pub struct ConstantMedium {
-    boundary: Rc<dyn Hittable>,
-    phase_function: Rc<dyn Material>,
+    boundary: Arc<dyn Hittable>,
+    phase_function: Arc<dyn Material>,
     neg_inv_density: f64,
 }
 
 impl ConstantMedium {
-    pub fn new(boundary: Rc<dyn Hittable>, density: f64, albedo: Box<dyn Texture>) -> Self {
+    pub fn new(boundary: Arc<dyn Hittable>, density: f64, albedo: Box<dyn Texture>) -> Self {
         Self {
             boundary,
-            phase_function: Rc::new(Isotropic::new(albedo)),
+            phase_function: Arc::new(Isotropic::new(albedo)),
             neg_inv_density: -1.0 / density,
         }
     }
-    pub fn new_from_color(boundary: Rc<dyn Hittable>, density: f64, color: Color) -> Self {
+    pub fn new_from_color(boundary: Arc<dyn Hittable>, density: f64, color: Color) -> Self {
         Self {
             boundary,
-            phase_function: Rc::new(Isotropic::new_from_color(color)),
+            phase_function: Arc::new(Isotropic::new_from_color(color)),
             neg_inv_density: -1.0 / density,
         }
     }
 }
 
 impl Hittable for ConstantMedium {
-    fn hit(&self, ray: &Ray, interval: &Interval) -> Option<HitRecord> {
-        if let Some(mut rec1) = self.boundary.hit(ray, &Interval::universe()) {
-            if let Some(mut rec2) = self
-                .boundary
-                .hit(ray, &Interval::new(rec1.t + 0.0001, f64::INFINITY))
+    fn hit(&self, ray: &Ray, interval: &Interval, rng: &mut dyn Rng) -> Option<HitRecord> {
+        if let Some(mut rec1) = self.boundary.hit(ray, &Interval::universe(), rng) {
+            if let Some(mut rec2) =
+                self.boundary
+                    .hit(ray, &Interval::new(rec1.t + 0.0001, f64::INFINITY), rng)
             {
                 rec1.t = f64::max(rec1.t, interval.min);
                 rec2.t = f64::min(rec2.t, interval.max);
@@ -206,7 +236,7 @@ impl Hittable for ConstantMedium {
 
                 let ray_length = ray.dir().length();
                 let distance_inside_boundary = (rec2.t - rec1.t) * ray_length;
-                let hit_distance = self.neg_inv_density * rand().ln();
+                let hit_distance = self.neg_inv_density * rng.next_f64().ln();
 
                 if hit_distance > distance_inside_boundary {
                     return None;
@@ -1,7 +1,7 @@
-use crate::{Aabb, HitRecord, Hittable, Interval, Material, Point3, Quad, Vec3};
-use std::rc::Rc;
+use crate::{Aabb, HitRecord, Hittable, Interval, Material, Point3, Quad, Rng, Vec3};
+use std::sync::Arc;
 pub struct HittableList {
-    objects: Vec<Rc<dyn Hittable>>,
+    objects: Vec<Arc<dyn Hittable>>,
     bbox: Aabb,
 }
 impl Default for HittableList {
@@ -16,12 +16,12 @@ impl HittableList {
             bbox: Aabb::default(),
         }
     }
-    pub fn new_from_node(node: Rc<dyn Hittable>) -> Self {
+    pub fn new_from_node(node: Arc<dyn Hittable>) -> Self {
         let mut list = Self::new();
         list.add(node);
         list
     }
-    pub fn add(&mut self, obj: Rc<dyn Hittable>) {
+    pub fn add(&mut self, obj: Arc<dyn Hittable>) {
         self.objects.push(obj);
         self.objects.iter().for_each(|o| {
             self.bbox = Aabb::new_from_aabb(self.bbox.clone(), o.bounding_box());
@@ -31,13 +31,13 @@ impl HittableList {
         self.objects.clear();
     }
 
-    pub fn list(&self) -> &Vec<Rc<dyn Hittable>> {
+    pub fn list(&self) -> &Vec<Arc<dyn Hittable>> {
         &self.objects
     }
 }
 
-impl From<HittableList> for Vec<Rc<dyn Hittable>> {
-    fn from(list: HittableList) -> Vec<Rc<dyn Hittable>> {
+impl From<HittableList> for Vec<Arc<dyn Hittable>> {
+    fn from(list: HittableList) -> Vec<Arc<dyn Hittable>> {
         list.objects
     }
 }
@@ -47,11 +47,12 @@ impl Hittable for HittableList {
         &self,
         ray: &crate::ray::Ray,
         interval: &Interval,
+        rng: &mut dyn Rng,
     ) -> Option<crate::hittable::HitRecord> {
         let mut close_so_far = interval.max;
         let mut result: Option<HitRecord> = None;
         for object in &self.objects {
-            if let Some(record) = object.hit(ray, &Interval::new(interval.min, close_so_far)) {
+            if let Some(record) = object.hit(ray, &Interval::new(interval.min, close_so_far), rng) {
                 close_so_far = record.t;
                 result = Some(record);
             }
@@ -61,9 +62,26 @@ impl Hittable for HittableList {
     fn bounding_box(&self) -> Aabb {
         self.bbox.clone()
     }
+
+    /// The average over the objects, which `random` picks between uniformly.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, rng: &mut dyn Rng) -> f64 {
+        let weight = 1.0 / self.objects.len() as f64;
+        self.objects
+            .iter()
+            .map(|o| weight * o.pdf_value(origin, direction, rng))
+            .sum()
+    }
+
+    fn random(&self, origin: &Point3, rng: &mut dyn Rng) -> Vec3 {
+        if self.objects.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+        let index = rng.range_i32(0, self.objects.len() as i32 - 1) as usize;
+        self.objects[index].random(origin, rng)
+    }
 }
 
-pub fn create_box(a: Point3, b: Point3, mat: Rc<dyn Material>) -> HittableList {
+pub fn create_box(a: Point3, b: Point3, mat: Arc<dyn Material>) -> HittableList {
     let mut sides = HittableList::new();
     let min = Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
     let max = Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
@@ -72,37 +90,37 @@ pub fn create_box(a: Point3, b: Point3, mat: Rc<dyn Material>) -> HittableList {
     let dy = Vec3::new(0.0, max.y() - min.y(), 0.0);
     let dz = Vec3::new(0.0, 0.0, max.z() - min.z());
 
-    sides.add(Rc::new(Quad::new(
+    sides.add(Arc::new(Quad::new(
         Point3::new(min.x(), min.y(), max.z()),
         dx,
         dy,
         mat.clone(),
     )));
-    sides.add(Rc::new(Quad::new(
+    sides.add(Arc::new(Quad::new(
         Point3::new(max.x(), min.y(), max.z()),
         -dz,
         dy,
         mat.clone(),
     )));
-    sides.add(Rc::new(Quad::new(
+    sides.add(Arc::new(Quad::new(
         Point3::new(max.x(), min.y(), min.z()),
         -dx,
         dy,
         mat.clone(),
     )));
-    sides.add(Rc::new(Quad::new(
+    sides.add(Arc::new(Quad::new(
         Point3::new(min.x(), min.y(), min.z()),
         dz,
         dy,
         mat.clone(),
     )));
-    sides.add(Rc::new(Quad::new(
+    sides.add(Arc::new(Quad::new(
         Point3::new(min.x(), max.y(), max.z()),
         dx,
         -dz,
         mat.clone(),
     )));
-    sides.add(Rc::new(Quad::new(
+    sides.add(Arc::new(Quad::new(
         Point3::new(min.x(), min.y(), min.z()),
         dx,
         dz,
@@ -1,5 +1,10 @@
+use std::path::Path;
+
 use image::io::Reader as ImageReader;
 
+use crate::{Error, Result};
+
+#[derive(Debug, Default)]
 pub struct Image {
     pub width: u32,
     pub height: u32,
@@ -7,16 +12,26 @@ pub struct Image {
 }
 
 impl Image {
-    pub fn new(path: &str) -> Self {
-        let img = ImageReader::open(path).unwrap().decode().unwrap();
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let img = ImageReader::open(path)
+            .map_err(|source| Error::Io {
+                path: path.to_path_buf(),
+                source,
+            })?
+            .decode()
+            .map_err(|source| Error::Image {
+                path: path.to_path_buf(),
+                source,
+            })?;
         let width = img.width();
         let height = img.height();
         let data = img.into_rgb8().into_raw();
-        Self {
+        Ok(Self {
             width,
             height,
             data,
-        }
+        })
     }
 
     fn clamp(&self, x: u32, min: u32, max: u32) -> u32 {
@@ -0,0 +1,671 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::{
+    power_heuristic, Color, HitRecord, Hittable, HittableList, HittablePdf, Interval, Lobe,
+    MixturePdf, Onb, PathStats, PathStatsCollector, Pdf, Ray, Rng, Termination, Vec3, INF,
+};
+
+/// Turns a camera ray into the color of its pixel sample.
+pub trait Integrator: Send + Sync {
+    /// The color seen along `ray`. `lights` is the part of `world` that may
+    /// be sampled directly, and may be empty.
+    fn ray_color(
+        &self,
+        ray: &Ray,
+        world: &HittableList,
+        lights: &HittableList,
+        rng: &mut dyn Rng,
+    ) -> Color;
+}
+
+/// The integrators a `CameraConfig` can pick by name.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegratorKind {
+    #[default]
+    Path,
+    #[serde(alias = "ao")]
+    AmbientOcclusion,
+    Normals,
+    Albedo,
+    Depth,
+    Whitted,
+}
+
+impl FromStr for IntegratorKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "path" => Ok(Self::Path),
+            "ao" | "ambient_occlusion" => Ok(Self::AmbientOcclusion),
+            "normals" => Ok(Self::Normals),
+            "albedo" => Ok(Self::Albedo),
+            "depth" => Ok(Self::Depth),
+            "whitted" => Ok(Self::Whitted),
+            _ => Err(format!("unknown integrator `{}`", s)),
+        }
+    }
+}
+
+/// How diffuse bounces find the scene's lights.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LightSampling {
+    /// Follow one direction drawn from an even mix of the lights and the
+    /// material.
+    Mixture,
+    /// Take one light sample and one material sample per bounce and weight
+    /// them with the power heuristic.
+    #[default]
+    Mis,
+}
+
+impl FromStr for LightSampling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mixture" => Ok(Self::Mixture),
+            "mis" => Ok(Self::Mis),
+            _ => Err(format!("unknown light sampling `{}`", s)),
+        }
+    }
+}
+
+fn black() -> Color {
+    Color::new(0.0, 0.0, 0.0)
+}
+
+fn first_hit(ray: &Ray, world: &HittableList, rng: &mut dyn Rng) -> Option<HitRecord> {
+    world.hit(ray, &Interval::new(0.001, INF), rng)
+}
+
+/// Unbiased Monte Carlo path tracing.
+pub struct PathTracer {
+    max_depth: usize,
+    background: Color,
+    light_sampling: LightSampling,
+    roulette_min_depth: usize,
+    stats: Option<Arc<dyn PathStatsCollector>>,
+}
+
+impl PathTracer {
+    pub fn new(max_depth: usize, background: Color) -> Self {
+        Self {
+            max_depth,
+            background,
+            light_sampling: LightSampling::default(),
+            roulette_min_depth: 5,
+            stats: None,
+        }
+    }
+
+    pub fn with_light_sampling(mut self, light_sampling: LightSampling) -> Self {
+        self.light_sampling = light_sampling;
+        self
+    }
+
+    /// From this bounce on, paths survive with a probability that follows
+    /// their throughput, which keeps deep paths cheap without biasing the
+    /// image. `max_depth` or more disables it.
+    pub fn with_roulette_min_depth(mut self, depth: usize) -> Self {
+        self.roulette_min_depth = depth;
+        self
+    }
+
+    /// Reports how every traced path ended to `stats`.
+    pub fn with_stats(mut self, stats: Arc<dyn PathStatsCollector>) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
+    /// Light reaching `record` from one sample of `lights`, times the
+    /// material's response for that direction and its MIS weight.
+    #[allow(clippy::too_many_arguments)]
+    fn sample_light(
+        &self,
+        ray: &Ray,
+        record: &HitRecord,
+        attenuation: Color,
+        surface_pdf: &dyn Pdf,
+        world: &HittableList,
+        lights: &HittableList,
+        rng: &mut dyn Rng,
+    ) -> Color {
+        let light_pdf = HittablePdf::new(lights, record.point);
+        let light_ray = Ray::new_with_time(record.point, light_pdf.generate(rng), ray.time());
+        let light_value = light_pdf.value(&light_ray.dir(), rng);
+        let f = record.mat.eval(ray, record, &light_ray, attenuation);
+        if light_value <= 0.0 || f.near_zero() {
+            return black();
+        }
+        let Some(hit) = first_hit(&light_ray, world, rng) else {
+            return black();
+        };
+        let emitted = hit.mat.emiited(hit.u, hit.v, &hit.point);
+        let bsdf_value = surface_pdf.value(&light_ray.dir(), rng);
+        f * emitted * power_heuristic(light_value, bsdf_value) / light_value
+    }
+}
+
+impl Integrator for PathTracer {
+    /// Radiance arriving along a camera ray, traced bounce by bounce.
+    /// Diffuse bounces also sample `lights` directly, as set by
+    /// `with_light_sampling`; an empty list leaves every bounce to the
+    /// materials.
+    fn ray_color(
+        &self,
+        ray: &Ray,
+        world: &HittableList,
+        lights: &HittableList,
+        rng: &mut dyn Rng,
+    ) -> Color {
+        let mut ray = ray.clone();
+        let mut radiance = black();
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        // Density with which a diffuse bounce under MIS picked `ray`, so that
+        // emission it finds is weighted against that bounce's light sample.
+        let mut bsdf_pdf: Option<f64> = None;
+        let mut bounce = 0;
+        let termination = loop {
+            if bounce >= self.max_depth {
+                break Termination::MaxDepth;
+            }
+            let Some(record) = first_hit(&ray, world, rng) else {
+                radiance += throughput * self.background;
+                break Termination::Escaped;
+            };
+            let mut emitted = record.mat.emiited(record.u, record.v, &record.point);
+            if let Some(bsdf_pdf) = bsdf_pdf {
+                if !emitted.near_zero() {
+                    let light_pdf = lights.pdf_value(&ray.origin(), &ray.dir(), rng);
+                    emitted *= power_heuristic(bsdf_pdf, light_pdf);
+                }
+            }
+            radiance += throughput * emitted;
+            let Some(srec) = record.mat.scatter(&ray, &record, rng) else {
+                break Termination::Absorbed;
+            };
+
+            // Past the minimum depth, end paths that carry little light at
+            // random, and boost the survivors so the expected value is
+            // unchanged.
+            let mut roulette_weight = 1.0;
+            if bounce >= self.roulette_min_depth {
+                let survival = throughput
+                    .x()
+                    .max(throughput.y())
+                    .max(throughput.z())
+                    .min(1.0);
+                if rng.next_f64() >= survival {
+                    break Termination::RussianRoulette;
+                }
+                roulette_weight = 1.0 / survival;
+            }
+            bounce += 1;
+
+            let surface_pdf = match srec.lobe {
+                Lobe::Specular(scattered) => {
+                    throughput = throughput * srec.attenuation * roulette_weight;
+                    bsdf_pdf = None;
+                    ray = scattered;
+                    continue;
+                }
+                Lobe::Diffuse(pdf) => pdf,
+            };
+            let time = ray.time();
+            let (scattered, pdf_value) = if lights.list().is_empty()
+                || self.light_sampling == LightSampling::Mixture
+            {
+                // One direction from an even mix of the lights and the
+                // material; without lights, just the material's lobe.
+                let light_pdf = HittablePdf::new(lights, record.point);
+                let mixture = MixturePdf::new(&light_pdf, surface_pdf.as_ref());
+                let pdf: &dyn Pdf = if lights.list().is_empty() {
+                    surface_pdf.as_ref()
+                } else {
+                    &mixture
+                };
+                let scattered = Ray::new_with_time(record.point, pdf.generate(rng), time);
+                if scattered.dir().near_zero() {
+                    break Termination::Absorbed;
+                }
+                bsdf_pdf = None;
+                let pdf_value = pdf.value(&scattered.dir(), rng);
+                (scattered, pdf_value)
+            } else {
+                // One sample towards a light. A bounce at the last depth
+                // couldn't see emission through its BSDF sample, so it
+                // skips this one too.
+                if bounce < self.max_depth {
+                    radiance += throughput
+                        * roulette_weight
+                        * self.sample_light(
+                            &ray,
+                            &record,
+                            srec.attenuation,
+                            surface_pdf.as_ref(),
+                            world,
+                            lights,
+                            rng,
+                        );
+                }
+                // One sample from the material, whose emission gets the
+                // other weight.
+                let scattered = Ray::new_with_time(record.point, surface_pdf.generate(rng), time);
+                if scattered.dir().near_zero() {
+                    break Termination::Absorbed;
+                }
+                let pdf_value = surface_pdf.value(&scattered.dir(), rng);
+                bsdf_pdf = Some(pdf_value);
+                (scattered, pdf_value)
+            };
+            if pdf_value <= 0.0 {
+                break Termination::Absorbed;
+            }
+            let f = record.mat.eval(&ray, &record, &scattered, srec.attenuation);
+            throughput = throughput * f * (roulette_weight / pdf_value);
+            ray = scattered;
+        };
+        if let Some(stats) = &self.stats {
+            stats.record(PathStats {
+                bounces: bounce,
+                termination,
+            });
+        }
+        radiance
+    }
+}
+
+/// White where the hemisphere above a hit is open within `radius`, darker
+/// the more of it is blocked. Misses are white.
+pub struct AmbientOcclusion {
+    radius: Option<f64>,
+}
+
+impl AmbientOcclusion {
+    /// Occlusion within a tenth of the world's bounding box diagonal.
+    pub fn new() -> Self {
+        Self { radius: None }
+    }
+
+    pub fn with_radius(mut self, radius: f64) -> Self {
+        self.radius = Some(radius);
+        self
+    }
+}
+
+impl Default for AmbientOcclusion {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn ray_color(
+        &self,
+        ray: &Ray,
+        world: &HittableList,
+        _lights: &HittableList,
+        rng: &mut dyn Rng,
+    ) -> Color {
+        let Some(record) = first_hit(ray, world, rng) else {
+            return Color::new(1.0, 1.0, 1.0);
+        };
+        let radius = self.radius.unwrap_or_else(|| {
+            let bbox = world.bounding_box();
+            0.1 * Vec3::new(bbox.x.size(), bbox.y.size(), bbox.z.size()).length()
+        });
+        // A cosine-weighted probe makes the pixel average the
+        // cosine-weighted visibility, with no division needed.
+        let probe = Onb::new(&record.normal).transform(&Vec3::random_cosine_direction(rng));
+        let probe = Ray::new_with_time(record.point, probe, ray.time());
+        match world.hit(
+            &probe,
+            &Interval::new(0.001, radius / probe.dir().length()),
+            rng,
+        ) {
+            Some(_) => black(),
+            None => Color::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+/// Outward surface normals, mapped from `[-1, 1]` to `[0, 1]` per axis.
+pub struct Normals;
+
+impl Integrator for Normals {
+    fn ray_color(
+        &self,
+        ray: &Ray,
+        world: &HittableList,
+        _lights: &HittableList,
+        rng: &mut dyn Rng,
+    ) -> Color {
+        let Some(record) = first_hit(ray, world, rng) else {
+            return black();
+        };
+        let outward = if record.front_face {
+            record.normal
+        } else {
+            -record.normal
+        };
+        0.5 * (outward.unit_vector() + Color::new(1.0, 1.0, 1.0))
+    }
+}
+
+/// The attenuation of the first surface hit, the emission of lights and the
+/// background elsewhere, with no lighting at all.
+pub struct Albedo {
+    background: Color,
+}
+
+impl Albedo {
+    pub fn new(background: Color) -> Self {
+        Self { background }
+    }
+}
+
+impl Integrator for Albedo {
+    fn ray_color(
+        &self,
+        ray: &Ray,
+        world: &HittableList,
+        _lights: &HittableList,
+        rng: &mut dyn Rng,
+    ) -> Color {
+        let Some(record) = first_hit(ray, world, rng) else {
+            return self.background;
+        };
+        match record.mat.scatter(ray, &record, rng) {
+            Some(srec) => srec.attenuation,
+            None => record.mat.emiited(record.u, record.v, &record.point),
+        }
+    }
+}
+
+/// Distance to the first hit divided by a maximum distance, so near is
+/// black and far is white. Misses are white.
+pub struct Depth {
+    max_distance: Option<f64>,
+}
+
+impl Depth {
+    /// Scales by the distance from the ray origin to the farthest corner of
+    /// the world's bounding box.
+    pub fn new() -> Self {
+        Self { max_distance: None }
+    }
+
+    pub fn with_max_distance(mut self, max_distance: f64) -> Self {
+        self.max_distance = Some(max_distance);
+        self
+    }
+}
+
+impl Default for Depth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Integrator for Depth {
+    fn ray_color(
+        &self,
+        ray: &Ray,
+        world: &HittableList,
+        _lights: &HittableList,
+        rng: &mut dyn Rng,
+    ) -> Color {
+        let Some(record) = first_hit(ray, world, rng) else {
+            return Color::new(1.0, 1.0, 1.0);
+        };
+        let max_distance = self.max_distance.unwrap_or_else(|| {
+            let bbox = world.bounding_box();
+            let o = ray.origin();
+            let far = |i: &Interval, x: f64| f64::max((i.min - x).abs(), (i.max - x).abs());
+            Vec3::new(
+                far(&bbox.x, o.x()),
+                far(&bbox.y, o.y()),
+                far(&bbox.z, o.z()),
+            )
+            .length()
+        });
+        let depth = (record.t * ray.dir().length() / max_distance).min(1.0);
+        Color::new(depth, depth, depth)
+    }
+}
+
+/// Classic recursive ray tracing: mirrors and glass are followed, diffuse
+/// surfaces only see one shadowed sample of `lights` (or the background
+/// when there are none), and indirect light is ignored. Far less noisy than
+/// path tracing for previews.
+pub struct Whitted {
+    max_depth: usize,
+    background: Color,
+}
+
+impl Whitted {
+    pub fn new(max_depth: usize, background: Color) -> Self {
+        Self {
+            max_depth,
+            background,
+        }
+    }
+
+    /// Light from one shadowed sample of `lights`, times the material's
+    /// response for that direction.
+    fn direct(
+        &self,
+        ray: &Ray,
+        record: &HitRecord,
+        attenuation: Color,
+        world: &HittableList,
+        lights: &HittableList,
+        rng: &mut dyn Rng,
+    ) -> Color {
+        if lights.list().is_empty() {
+            return attenuation * self.background;
+        }
+        let light_pdf = HittablePdf::new(lights, record.point);
+        let light_ray = Ray::new_with_time(record.point, light_pdf.generate(rng), ray.time());
+        let light_value = light_pdf.value(&light_ray.dir(), rng);
+        let f = record.mat.eval(ray, record, &light_ray, attenuation);
+        if light_value <= 0.0 || f.near_zero() {
+            return black();
+        }
+        let Some(hit) = first_hit(&light_ray, world, rng) else {
+            return black();
+        };
+        f * hit.mat.emiited(hit.u, hit.v, &hit.point) / light_value
+    }
+}
+
+impl Integrator for Whitted {
+    fn ray_color(
+        &self,
+        ray: &Ray,
+        world: &HittableList,
+        lights: &HittableList,
+        rng: &mut dyn Rng,
+    ) -> Color {
+        let mut ray = ray.clone();
+        let mut radiance = black();
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        for _ in 0..self.max_depth {
+            let Some(record) = first_hit(&ray, world, rng) else {
+                return radiance + throughput * self.background;
+            };
+            radiance += throughput * record.mat.emiited(record.u, record.v, &record.point);
+            let Some(srec) = record.mat.scatter(&ray, &record, rng) else {
+                break;
+            };
+            match srec.lobe {
+                Lobe::Specular(scattered) => {
+                    throughput = throughput * srec.attenuation;
+                    ray = scattered;
+                }
+                Lobe::Diffuse(_) => {
+                    let direct = self.direct(&ray, &record, srec.attenuation, world, lights, rng);
+                    return radiance + throughput * direct;
+                }
+            }
+        }
+        radiance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{AmbientOcclusion, Depth, Integrator, LightSampling, Normals, PathTracer, Whitted};
+    use crate::{
+        Camera, CameraConfig, Color, DiffuseLight, HittableList, Lambertian, PathStatsSummary,
+        Pcg32, Point3, Quad, Ray, Termination, Vec3,
+    };
+
+    /// A white floor under a small light, which every strategy must agree on.
+    fn lit_floor() -> (HittableList, HittableList, Camera) {
+        let white = Arc::new(Lambertian::new_from_color(Color::new(0.8, 0.8, 0.8)));
+        let light = Arc::new(DiffuseLight::new_from_color(Color::new(10.0, 10.0, 10.0)));
+        let mut world = HittableList::new();
+        world.add(Arc::new(Quad::new(
+            Point3::new(-5.0, 0.0, -5.0),
+            Vec3::new(10.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 10.0),
+            white,
+        )));
+        let lamp: Arc<Quad> = Arc::new(Quad::new(
+            Point3::new(-0.5, 2.0, -0.5),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            light,
+        ));
+        world.add(lamp.clone());
+        let cam = CameraConfig {
+            image_width: 8,
+            lookfrom: Point3::new(0.0, 1.0, 3.0),
+            lookat: Point3::new(0.0, 0.0, 0.0),
+            sample_per_pixel: 200,
+            max_depth: 4,
+            fov: 60.0,
+            ..Default::default()
+        }
+        .build()
+        .with_threads(1);
+        (world, HittableList::new_from_node(lamp), cam)
+    }
+
+    fn path_tracer() -> PathTracer {
+        PathTracer::new(4, Color::new(0.0, 0.0, 0.0))
+    }
+
+    fn mean(cam: Camera, world: &HittableList, lights: &HittableList) -> f64 {
+        let fb = cam.render(world, lights);
+        fb.pixels().iter().map(|c| c.x()).sum::<f64>() / fb.pixels().len() as f64
+    }
+
+    #[test]
+    fn light_sampling_strategies_agree() {
+        let (world, lights, cam) = lit_floor();
+        let with = |light_sampling| Arc::new(path_tracer().with_light_sampling(light_sampling));
+        let mis = mean(
+            cam.with_integrator(with(LightSampling::Mis)),
+            &world,
+            &lights,
+        );
+        let (_, _, cam) = lit_floor();
+        let mixture = mean(
+            cam.with_integrator(with(LightSampling::Mixture)),
+            &world,
+            &lights,
+        );
+        let (_, _, cam) = lit_floor();
+        let unsampled = mean(cam, &world, &HittableList::new());
+        assert!((mis - mixture).abs() < 0.05 * mis, "{} vs {}", mis, mixture);
+        assert!(
+            (mis - unsampled).abs() < 0.1 * mis,
+            "{} vs {}",
+            mis,
+            unsampled
+        );
+    }
+
+    #[test]
+    fn roulette_keeps_the_mean() {
+        let mean = |roulette_min_depth| {
+            let (world, lights, cam) = lit_floor();
+            let integrator = path_tracer().with_roulette_min_depth(roulette_min_depth);
+            mean(cam.with_integrator(Arc::new(integrator)), &world, &lights)
+        };
+        let (always, never) = (mean(0), mean(4));
+        assert!(
+            (always - never).abs() < 0.05 * never,
+            "{} vs {}",
+            always,
+            never
+        );
+    }
+
+    #[test]
+    fn every_path_is_counted() {
+        let (world, lights, cam) = lit_floor();
+        let stats = Arc::new(PathStatsSummary::new());
+        let fb = cam
+            .with_integrator(Arc::new(path_tracer().with_stats(stats.clone())))
+            .render(&world, &lights);
+        let paths = (fb.pixels().len() * 200) as u64;
+        assert_eq!(stats.paths(), paths);
+        let ended: u64 = Termination::ALL.iter().map(|&t| stats.terminated(t)).sum();
+        assert_eq!(ended, paths);
+        // Paths that find the lamp end there, and none outlives max_depth.
+        assert!(stats.terminated(Termination::Absorbed) > 0);
+        assert!(stats.max_bounces() <= 4);
+    }
+
+    #[test]
+    fn whitted_matches_direct_light() {
+        // With one bounce, path tracing only sees direct light, which is all
+        // Whitted computes on a diffuse floor.
+        let (world, lights, cam) = lit_floor();
+        let whitted = mean(
+            cam.with_integrator(Arc::new(Whitted::new(4, Color::new(0.0, 0.0, 0.0)))),
+            &world,
+            &lights,
+        );
+        let (_, _, cam) = lit_floor();
+        let direct = PathTracer::new(2, Color::new(0.0, 0.0, 0.0));
+        let direct = mean(cam.with_integrator(Arc::new(direct)), &world, &lights);
+        assert!(
+            (whitted - direct).abs() < 0.05 * direct,
+            "{} vs {}",
+            whitted,
+            direct
+        );
+    }
+
+    #[test]
+    fn debug_views_of_the_floor() {
+        let (world, lights, _) = lit_floor();
+        let mut rng = Pcg32::new(0, 0);
+        let down = Ray::new(Point3::new(2.0, 1.0, 2.0), Vec3::new(0.0, -1.0, 0.0));
+        // The floor's u × v points down, and the view shows it from above.
+        let normal = Normals.ray_color(&down, &world, &lights, &mut rng);
+        assert_eq!(normal, Color::new(0.5, 0.0, 0.5));
+        let depth = Depth::new()
+            .with_max_distance(4.0)
+            .ray_color(&down, &world, &lights, &mut rng);
+        assert!((depth.x() - 0.25).abs() < 1e-9);
+        // Nothing but the lamp is above the floor, and it is out of reach.
+        let ao = AmbientOcclusion::new().with_radius(1.0);
+        let open = (0..100)
+            .map(|_| ao.ray_color(&down, &world, &lights, &mut rng).x())
+            .sum::<f64>();
+        assert_eq!(open, 100.0);
+    }
+}
@@ -2,49 +2,79 @@ pub mod aabb;
 pub mod bvh;
 pub mod camera;
 pub mod color;
+pub mod error;
+pub mod framebuffer;
 pub mod hittable;
 pub mod hittable_list;
 pub mod image;
+pub mod integrator;
 pub mod interval;
 pub mod material;
+pub mod mesh;
+pub mod microfacet;
+pub mod obj;
+pub mod onb;
+pub mod output;
+pub mod pdf;
 pub mod perlin;
+pub mod principled;
 pub mod quad;
 pub mod ray;
+pub mod rng;
+pub mod sampler;
+pub mod scene;
 pub mod sphere;
+pub mod stats;
 pub mod texture;
+pub mod triangle;
 pub mod vec3;
 
 pub use aabb::Aabb;
-pub use bvh::BVHNode;
-pub use camera::Camera;
-pub use color::{write_color, Color};
+pub use bvh::{BVHNode, BvhBuilder, BvhStats};
+pub use camera::{Camera, CameraConfig};
+pub use color::{color_to_rgb16, color_to_rgb8, write_color, Color};
+pub use error::{Error, Result};
+pub use framebuffer::Framebuffer;
 pub use hittable::{ConstantMedium, HitRecord, Hittable, RotateY, Translate};
 pub use hittable_list::{create_box, HittableList};
 pub use image::Image;
+pub use integrator::{
+    Albedo, AmbientOcclusion, Depth, Integrator, IntegratorKind, LightSampling, Normals,
+    PathTracer, Whitted,
+};
 pub use interval::Interval;
-pub use material::{Dielectric, DiffuseLight, Isotropic, Lambertian, Material, Metal};
+pub use material::{
+    BumpMap, Conductor, Dielectric, DiffuseLight, Isotropic, Lambertian, Lobe, Material, Metal,
+    NormalMap, RoughDielectric, ScatterRecord,
+};
+pub use mesh::{Face, TriangleMesh};
+pub use microfacet::{
+    fresnel_complex, fresnel_dielectric, GgxDielectric, GgxDielectricPdf, GgxReflectionPdf,
+    TrowbridgeReitz,
+};
+pub use obj::load_obj;
+pub use onb::Onb;
+pub use output::{write_exr_layers, FileSink, Format, PpmSink, Sink};
+pub use pdf::{power_heuristic, CosinePdf, HittablePdf, MixturePdf, Pdf, SpherePdf};
 pub use perlin::Perlin;
+pub use principled::Principled;
 pub use quad::Quad;
 pub use ray::Ray;
+pub use rng::{Pcg32, Rng};
+pub use sampler::{
+    BlueNoiseSampler, HaltonSampler, IndependentSampler, Sampler, SamplerKind, SobolSampler,
+    StratifiedSampler,
+};
+pub use scene::{load_scene, parse_scene, Scene};
 pub use sphere::Sphere;
-pub use texture::{CheckerTexture, NoiseTexture, SolidColor, Texture};
+pub use stats::{PathStats, PathStatsCollector, PathStatsSummary, Termination};
+pub use texture::{CheckerTexture, ImageTexture, NoiseTexture, SolidColor, Texture};
+pub use triangle::Triangle;
 pub use vec3::{Point3, Vec3};
 
-pub const INF: f64 = std::f64::INFINITY;
+pub const INF: f64 = f64::INFINITY;
 pub const PI: f64 = std::f64::consts::PI;
 
 pub fn deg2rad(deg: f64) -> f64 {
     deg / 180.0 * PI
 }
-
-pub fn rand() -> f64 {
-    rand::random::<f64>()
-}
-
-pub fn rand_range(min: f64, max: f64) -> f64 {
-    min + (max - min) * rand()
-}
-
-pub fn rand_i32(min: i32, max: i32) -> i32 {
-    rand_range(min as f64, max as f64 + 1.0) as i32
-}
@@ -1,12 +1,45 @@
 use std::ops::Neg;
+use std::sync::Arc;
 
-use crate::{rand, Color, HitRecord, Ray, SolidColor, Texture, Vec3};
+use crate::{
+    fresnel_complex, fresnel_dielectric, Color, CosinePdf, GgxDielectric, GgxDielectricPdf,
+    GgxReflectionPdf, HitRecord, Onb, Pdf, Ray, Rng, SolidColor, SpherePdf, Texture,
+    TrowbridgeReitz, Vec3, PI,
+};
 
-pub trait Material {
-    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Color, Ray)>;
+/// How light leaving a surface is spread over directions.
+pub enum Lobe {
+    /// Exactly one outgoing ray, chosen by the material. Mirrors and glass
+    /// can't be importance sampled, so this ray is followed as is.
+    Specular(Ray),
+    /// A spread of directions with a known density, which the integrator
+    /// samples and may mix with others, e.g. towards lights.
+    Diffuse(Box<dyn Pdf>),
+}
+
+/// The outcome of a scattering event.
+pub struct ScatterRecord {
+    pub attenuation: Color,
+    pub lobe: Lobe,
+}
+
+pub trait Material: Send + Sync {
+    fn scatter(&self, ray: &Ray, record: &HitRecord, rng: &mut dyn Rng) -> Option<ScatterRecord>;
     fn emiited(&self, _u: f64, _v: f64, _point: &Vec3) -> Color {
         Color::new(0.0, 0.0, 0.0)
     }
+    /// Density with which a diffuse lobe scatters `ray` into `scattered`.
+    fn scattering_pdf(&self, _ray: &Ray, _record: &HitRecord, _scattered: &Ray) -> f64 {
+        0.0
+    }
+    /// How much of the light arriving along `scattered` a diffuse lobe sends
+    /// back along `ray`: the BSDF times the cosine at the surface.
+    /// `attenuation` is what `scatter` returned for this hit, and the
+    /// default suits materials whose lobe is `attenuation` spread by
+    /// `scattering_pdf`.
+    fn eval(&self, ray: &Ray, record: &HitRecord, scattered: &Ray, attenuation: Color) -> Color {
+        attenuation * self.scattering_pdf(ray, record, scattered)
+    }
 }
 
 pub struct Lambertian {
@@ -26,15 +59,16 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Color, Ray)> {
-        let mut scatter_direction = record.normal + Vec3::random_unit_vector();
-        if scatter_direction.near_zero() {
-            scatter_direction = record.normal;
-        }
-        Some((
-            self.albedo.value(record.u, record.v, &record.point),
-            Ray::new_with_time(record.point, scatter_direction, ray.time()),
-        ))
+    fn scatter(&self, _ray: &Ray, record: &HitRecord, _rng: &mut dyn Rng) -> Option<ScatterRecord> {
+        Some(ScatterRecord {
+            attenuation: self.albedo.value(record.u, record.v, &record.point),
+            lobe: Lobe::Diffuse(Box::new(CosinePdf::new(&record.normal))),
+        })
+    }
+
+    fn scattering_pdf(&self, _ray: &Ray, record: &HitRecord, scattered: &Ray) -> f64 {
+        let cos_theta = record.normal.dot(&scattered.dir().unit_vector());
+        f64::max(0.0, cos_theta / PI)
     }
 }
 
@@ -50,28 +84,168 @@ impl Metal {
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(&self, ray: &Ray, record: &HitRecord, rng: &mut dyn Rng) -> Option<ScatterRecord> {
         let reflected = ray.dir().unit_vector().reflect(&record.normal);
         let scattered = Ray::new_with_time(
             record.point,
-            reflected + self.fuzz * Vec3::random_unit_vector(),
+            reflected + self.fuzz * Vec3::random_unit_vector(rng),
             ray.time(),
         );
         if scattered.dir().dot(&record.normal) > 0.0 {
-            Some((self.albedo, scattered))
+            Some(ScatterRecord {
+                attenuation: self.albedo,
+                lobe: Lobe::Specular(scattered),
+            })
         } else {
             None
         }
     }
 }
 
+/// A metal with GGX microfacets, its color coming from the complex index of
+/// refraction `eta + i k` per RGB channel. Roughness is read from the first
+/// channel of a texture.
+pub struct Conductor {
+    eta: Color,
+    k: Color,
+    roughness: Box<dyn Texture>,
+}
+
+impl Conductor {
+    pub fn new(eta: Color, k: Color, roughness: Box<dyn Texture>) -> Self {
+        Self { eta, k, roughness }
+    }
+
+    pub fn gold(roughness: Box<dyn Texture>) -> Self {
+        Self::new(
+            Color::new(0.143, 0.374, 1.442),
+            Color::new(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: Box<dyn Texture>) -> Self {
+        Self::new(
+            Color::new(0.200, 0.924, 1.102),
+            Color::new(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: Box<dyn Texture>) -> Self {
+        Self::new(
+            Color::new(1.657, 0.880, 0.521),
+            Color::new(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: Box<dyn Texture>) -> Self {
+        Self::new(
+            Color::new(0.155, 0.117, 0.138),
+            Color::new(4.828, 3.122, 2.147),
+            roughness,
+        )
+    }
+
+    fn distribution(&self, record: &HitRecord) -> TrowbridgeReitz {
+        let roughness = self.roughness.value(record.u, record.v, &record.point);
+        TrowbridgeReitz::new_from_roughness(roughness.x())
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(&self, ray: &Ray, record: &HitRecord, _rng: &mut dyn Rng) -> Option<ScatterRecord> {
+        let wo = -ray.dir().unit_vector();
+        let cos_theta = wo.dot(&record.normal);
+        if cos_theta <= 0.0 {
+            return None;
+        }
+        let distribution = self.distribution(record);
+        if distribution.is_smooth() {
+            let reflected = (-wo).reflect(&record.normal);
+            return Some(ScatterRecord {
+                attenuation: fresnel_complex(cos_theta, self.eta, self.k),
+                lobe: Lobe::Specular(Ray::new_with_time(record.point, reflected, ray.time())),
+            });
+        }
+        // The lobe's color depends on the direction, so `eval` works it out;
+        // the attenuation is only the reflectance head on.
+        Some(ScatterRecord {
+            attenuation: fresnel_complex(1.0, self.eta, self.k),
+            lobe: Lobe::Diffuse(Box::new(GgxReflectionPdf::new(
+                &record.normal,
+                &wo,
+                distribution,
+            ))),
+        })
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, scattered: &Ray, _attenuation: Color) -> Color {
+        let uvw = Onb::new(&record.normal);
+        let wo = uvw.to_local(&-ray.dir().unit_vector());
+        let wi = uvw.to_local(&scattered.dir().unit_vector());
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Color::zero();
+        }
+        let wm = (wo + wi).unit_vector();
+        let distribution = self.distribution(record);
+        let fresnel = fresnel_complex(wo.dot(&wm), self.eta, self.k);
+        fresnel * (distribution.d(&wm) * distribution.g(&wo, &wi) / (4.0 * wo.z()))
+    }
+}
+
 pub struct Dielectric {
     ir: f64,
+    /// Absorption coefficient per unit of distance travelled inside.
+    absorption: Color,
 }
 
 impl Dielectric {
     pub fn new(ir: f64) -> Self {
-        Self { ir }
+        Self {
+            ir,
+            absorption: Color::zero(),
+        }
+    }
+
+    /// Tints the glass by absorbing light along the way through it, following
+    /// Beer-Lambert: after a distance `d` a fraction `exp(-absorption * d)` is
+    /// left.
+    pub fn with_absorption(mut self, absorption: Color) -> Self {
+        self.absorption = absorption;
+        self
+    }
+
+    /// Tints the glass so that `color` is what's left of white light after
+    /// travelling `distance` through it. `distance` must be positive and
+    /// every channel of `color` in `(0, 1]`: anything else gives an infinite,
+    /// NaN or negative absorption.
+    pub fn with_transmittance(self, color: Color, distance: f64) -> Self {
+        debug_assert!(distance > 0.0, "distance {} isn't positive", distance);
+        let absorption = |c: f64| {
+            debug_assert!(c > 0.0 && c <= 1.0, "transmittance {} not in (0, 1]", c);
+            -c.ln() / distance
+        };
+        self.with_absorption(Color::new(
+            absorption(color.x()),
+            absorption(color.y()),
+            absorption(color.z()),
+        ))
+    }
+
+    /// What's left after travelling inside from the ray's origin to the hit.
+    fn transmittance(&self, ray: &Ray, record: &HitRecord) -> Color {
+        if record.front_face {
+            return Color::new(1.0, 1.0, 1.0);
+        }
+        let distance = record.t * ray.dir().length();
+        let left = |a: f64| (-a * distance).exp();
+        Color::new(
+            left(self.absorption.x()),
+            left(self.absorption.y()),
+            left(self.absorption.z()),
+        )
     }
 
     fn reflectance(cos: f64, ref_idx: f64) -> f64 {
@@ -82,7 +256,7 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(&self, ray: &Ray, record: &HitRecord, rng: &mut dyn Rng) -> Option<ScatterRecord> {
         let refraction_ratio = if record.front_face {
             1.0 / self.ir
         } else {
@@ -93,26 +267,79 @@ impl Material for Dielectric {
         let cos_theta = unit_dir.neg().dot(&record.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
 
-        if refraction_ratio * sin_theta > 1.0
-            || Dielectric::reflectance(cos_theta, refraction_ratio) > rand()
+        let direction = if refraction_ratio * sin_theta > 1.0
+            || Dielectric::reflectance(cos_theta, refraction_ratio) > rng.next_f64()
         {
-            Some((
-                Color::new(1.0, 1.0, 1.0),
-                Ray::new_with_time(record.point, unit_dir.reflect(&record.normal), ray.time()),
-            ))
+            unit_dir.reflect(&record.normal)
         } else {
-            Some((
-                Color::new(1.0, 1.0, 1.0),
-                Ray::new_with_time(
-                    record.point,
-                    unit_dir.refract(&record.normal, refraction_ratio),
-                    ray.time(),
-                ),
-            ))
+            unit_dir.refract(&record.normal, refraction_ratio)
+        };
+        Some(ScatterRecord {
+            attenuation: self.transmittance(ray, record),
+            lobe: Lobe::Specular(Ray::new_with_time(record.point, direction, ray.time())),
+        })
+    }
+}
+
+/// Glass with GGX microfacets on its surface, e.g. frosted glass or ice,
+/// using the exact Fresnel equations. Roughness is read from the first
+/// channel of a texture; where it is zero the surface is smooth.
+pub struct RoughDielectric {
+    ir: f64,
+    roughness: Box<dyn Texture>,
+}
+
+impl RoughDielectric {
+    pub fn new(ir: f64, roughness: Box<dyn Texture>) -> Self {
+        Self { ir, roughness }
+    }
+
+    fn bsdf(&self, record: &HitRecord) -> GgxDielectric {
+        let roughness = self.roughness.value(record.u, record.v, &record.point);
+        GgxDielectric {
+            distribution: TrowbridgeReitz::new_from_roughness(roughness.x()),
+            eta: if record.front_face {
+                self.ir
+            } else {
+                1.0 / self.ir
+            },
         }
     }
 }
 
+impl Material for RoughDielectric {
+    fn scatter(&self, ray: &Ray, record: &HitRecord, rng: &mut dyn Rng) -> Option<ScatterRecord> {
+        let unit_dir = ray.dir().unit_vector();
+        let bsdf = self.bsdf(record);
+        let lobe = if bsdf.distribution.is_smooth() {
+            let cos_theta = unit_dir.neg().dot(&record.normal);
+            let direction = if fresnel_dielectric(cos_theta, bsdf.eta) > rng.next_f64() {
+                unit_dir.reflect(&record.normal)
+            } else {
+                unit_dir.refract(&record.normal, 1.0 / bsdf.eta)
+            };
+            Lobe::Specular(Ray::new_with_time(record.point, direction, ray.time()))
+        } else {
+            Lobe::Diffuse(Box::new(GgxDielectricPdf::new(
+                &record.normal,
+                &unit_dir.neg(),
+                bsdf,
+            )))
+        };
+        Some(ScatterRecord {
+            attenuation: Color::new(1.0, 1.0, 1.0),
+            lobe,
+        })
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, scattered: &Ray, attenuation: Color) -> Color {
+        let uvw = Onb::new(&record.normal);
+        let wo = uvw.to_local(&-ray.dir().unit_vector());
+        let wi = uvw.to_local(&scattered.dir().unit_vector());
+        attenuation * self.bsdf(record).f(&wo, &wi)
+    }
+}
+
 pub struct DiffuseLight {
     emit: Box<dyn Texture>,
 }
@@ -130,7 +357,12 @@ impl DiffuseLight {
 }
 
 impl Material for DiffuseLight {
-    fn scatter(&self, _ray: &Ray, _record: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(
+        &self,
+        _ray: &Ray,
+        _record: &HitRecord,
+        _rng: &mut dyn Rng,
+    ) -> Option<ScatterRecord> {
         None
     }
 
@@ -156,10 +388,393 @@ impl Isotropic {
 }
 
 impl Material for Isotropic {
-    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Color, Ray)> {
-        Some((
-            self.albedo.value(record.u, record.v, &record.point),
-            Ray::new_with_time(record.point, Vec3::random_unit_vector(), ray.time()),
-        ))
+    fn scatter(&self, _ray: &Ray, record: &HitRecord, _rng: &mut dyn Rng) -> Option<ScatterRecord> {
+        Some(ScatterRecord {
+            attenuation: self.albedo.value(record.u, record.v, &record.point),
+            lobe: Lobe::Diffuse(Box::new(SpherePdf)),
+        })
+    }
+
+    fn scattering_pdf(&self, _ray: &Ray, _record: &HitRecord, _scattered: &Ray) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+}
+
+/// The outward normal at a hit and its tangents, made up around the normal
+/// when the shape has none.
+fn tangent_frame(record: &HitRecord) -> (Vec3, Vec3, Vec3) {
+    let normal = if record.front_face {
+        record.normal
+    } else {
+        -record.normal
+    };
+    if record.tangent.cross(&record.bitangent).near_zero() {
+        let tangent = Onb::new(&normal).u();
+        return (normal, tangent, normal.cross(&tangent));
+    }
+    (normal, record.tangent, record.bitangent)
+}
+
+/// A copy of `record` that shades with `normal`, given facing outwards.
+fn with_shading_normal(record: &HitRecord, normal: Vec3) -> HitRecord {
+    let mut shading = record.clone();
+    shading.normal = if record.front_face { normal } else { -normal };
+    shading
+}
+
+/// Shades another material with normals from a tangent space normal map,
+/// usually an `ImageTexture`. Red, green and blue hold the normal's
+/// components along `u`, along `v` and out of the surface, mapped from
+/// `[-1, 1]` to `[0, 1]`.
+pub struct NormalMap {
+    material: Arc<dyn Material>,
+    map: Box<dyn Texture>,
+}
+
+impl NormalMap {
+    pub fn new(material: Arc<dyn Material>, map: Box<dyn Texture>) -> Self {
+        Self { material, map }
+    }
+
+    fn shade(&self, record: &HitRecord) -> HitRecord {
+        let (normal, tangent, bitangent) = tangent_frame(record);
+        let tangent = (tangent - normal.dot(&tangent) * normal).unit_vector();
+        let mut bitangent_dir = normal.cross(&tangent);
+        if bitangent_dir.dot(&bitangent) < 0.0 {
+            // Mirrored texture coordinates.
+            bitangent_dir = -bitangent_dir;
+        }
+        let texel = 2.0 * self.map.value(record.u, record.v, &record.point) - 1.0;
+        let mapped = texel.x() * tangent + texel.y() * bitangent_dir + texel.z() * normal;
+        with_shading_normal(record, mapped.unit_vector())
+    }
+}
+
+impl Material for NormalMap {
+    fn scatter(&self, ray: &Ray, record: &HitRecord, rng: &mut dyn Rng) -> Option<ScatterRecord> {
+        self.material.scatter(ray, &self.shade(record), rng)
+    }
+
+    fn emiited(&self, u: f64, v: f64, point: &Vec3) -> Color {
+        self.material.emiited(u, v, point)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, record: &HitRecord, scattered: &Ray) -> f64 {
+        self.material
+            .scattering_pdf(ray, &self.shade(record), scattered)
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, scattered: &Ray, attenuation: Color) -> Color {
+        self.material
+            .eval(ray, &self.shade(record), scattered, attenuation)
+    }
+}
+
+/// Shades another material as if its surface were raised along the normal
+/// by `scale` times a height texture's first channel. Any texture works,
+/// e.g. a `NoiseTexture` for a rough plaster.
+pub struct BumpMap {
+    material: Arc<dyn Material>,
+    height: Box<dyn Texture>,
+    scale: f64,
+    step: f64,
+}
+
+impl BumpMap {
+    pub fn new(material: Arc<dyn Material>, height: Box<dyn Texture>, scale: f64) -> Self {
+        Self {
+            material,
+            height,
+            scale,
+            step: 1e-3,
+        }
+    }
+
+    /// Step in `u` and `v` of the finite differences that find the slope.
+    /// For an image, about one texel.
+    pub fn with_step(mut self, step: f64) -> Self {
+        self.step = step;
+        self
+    }
+
+    fn shade(&self, record: &HitRecord) -> HitRecord {
+        let (normal, dpdu, dpdv) = tangent_frame(record);
+        let height = |du: f64, dv: f64| {
+            let point = record.point + du * dpdu + dv * dpdv;
+            let value = self.height.value(record.u + du, record.v + dv, &point);
+            self.scale * value.x()
+        };
+        let base = height(0.0, 0.0);
+        let dpdu = dpdu + (height(self.step, 0.0) - base) / self.step * normal;
+        let dpdv = dpdv + (height(0.0, self.step) - base) / self.step * normal;
+        let bumped = dpdu.cross(&dpdv).unit_vector();
+        let bumped = if bumped.dot(&normal) < 0.0 {
+            -bumped
+        } else {
+            bumped
+        };
+        with_shading_normal(record, bumped)
+    }
+}
+
+impl Material for BumpMap {
+    fn scatter(&self, ray: &Ray, record: &HitRecord, rng: &mut dyn Rng) -> Option<ScatterRecord> {
+        self.material.scatter(ray, &self.shade(record), rng)
+    }
+
+    fn emiited(&self, u: f64, v: f64, point: &Vec3) -> Color {
+        self.material.emiited(u, v, point)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, record: &HitRecord, scattered: &Ray) -> f64 {
+        self.material
+            .scattering_pdf(ray, &self.shade(record), scattered)
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, scattered: &Ray, attenuation: Color) -> Color {
+        self.material
+            .eval(ray, &self.shade(record), scattered, attenuation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{
+        BumpMap, Conductor, Dielectric, Lambertian, Lobe, Material, Metal, NormalMap,
+        RoughDielectric,
+    };
+    use crate::{
+        Color, HitRecord, Hittable, Interval, Pcg32, Point3, Quad, Ray, RotateY, SolidColor,
+        Sphere, Texture, Translate, Vec3, INF, PI,
+    };
+
+    fn record(mat: Arc<dyn Material>) -> (Ray, HitRecord) {
+        let ray = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.3, -1.0, 0.1));
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        let record = HitRecord::new(&ray, Point3::default(), normal, 1.0, mat, 0.0, 0.0);
+        (ray, record)
+    }
+
+    #[test]
+    fn lambertian_samples_its_own_density() {
+        let mut rng = Pcg32::new(0, 0);
+        let mat = Arc::new(Lambertian::new_from_color(Color::new(0.5, 0.5, 0.5)));
+        let (ray, record) = record(mat.clone());
+        let Some(Lobe::Diffuse(pdf)) = mat.scatter(&ray, &record, &mut rng).map(|s| s.lobe) else {
+            panic!("lambertian should have a diffuse lobe");
+        };
+        for _ in 0..100 {
+            let scattered = Ray::new(record.point, pdf.generate(&mut rng));
+            assert!(scattered.dir().dot(&record.normal) >= 0.0);
+            let expected = mat.scattering_pdf(&ray, &record, &scattered);
+            assert!((pdf.value(&scattered.dir(), &mut rng) - expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn metal_is_specular() {
+        let mut rng = Pcg32::new(0, 0);
+        let mat = Arc::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.0));
+        let (ray, record) = record(mat.clone());
+        let srec = mat.scatter(&ray, &record, &mut rng).unwrap();
+        let Lobe::Specular(scattered) = srec.lobe else {
+            panic!("metal should be specular");
+        };
+        let expected = ray.dir().unit_vector().reflect(&record.normal);
+        assert!((scattered.dir() - expected).length() < 1e-12);
+    }
+
+    #[test]
+    fn conductor_samples_match_its_response() {
+        // Importance sampling the lobe and sampling the hemisphere uniformly
+        // must estimate the same albedo, and a perfect reflector keeps at
+        // most all of the light.
+        let mut rng = Pcg32::new(4, 0);
+        let roughness = Box::new(SolidColor::new(Color::new(0.5, 0.5, 0.5)));
+        let mirror = Color::new(1e3, 1e3, 1e3);
+        let mat = Arc::new(Conductor::new(Color::new(1.0, 1.0, 1.0), mirror, roughness));
+        let (ray, record) = record(mat.clone());
+        let Some(srec) = mat.scatter(&ray, &record, &mut rng) else {
+            panic!("conductor should scatter");
+        };
+        let Lobe::Diffuse(pdf) = srec.lobe else {
+            panic!("rough conductor should have a sampled lobe");
+        };
+        let n = 200_000;
+        let mut sampled = 0.0;
+        let mut uniform = 0.0;
+        for _ in 0..n {
+            let scattered = Ray::new(record.point, pdf.generate(&mut rng));
+            let value = if scattered.dir().near_zero() {
+                0.0
+            } else {
+                pdf.value(&scattered.dir(), &mut rng)
+            };
+            if value > 0.0 {
+                let f = mat.eval(&ray, &record, &scattered, srec.attenuation);
+                sampled += f.x() / value;
+            }
+            let hemisphere = Vec3::random_on_hemisphere(&mut rng, &record.normal);
+            let f = mat.eval(
+                &ray,
+                &record,
+                &Ray::new(record.point, hemisphere),
+                srec.attenuation,
+            );
+            uniform += f.x() * 2.0 * PI;
+        }
+        let (sampled, uniform) = (sampled / n as f64, uniform / n as f64);
+        assert!(sampled <= 1.0 && sampled > 0.8, "{}", sampled);
+        assert!(
+            (sampled - uniform).abs() < 0.02,
+            "{} vs {}",
+            sampled,
+            uniform
+        );
+    }
+
+    #[test]
+    fn tinted_glass_absorbs_inside() {
+        let (ray, _) = record(Arc::new(Metal::new(Color::zero(), 0.0)));
+        let tint = Color::new(0.25, 0.5, 1.0);
+        let mat: Arc<dyn Material> =
+            Arc::new(Dielectric::new(1.5).with_transmittance(tint, ray.dir().length()));
+        let mut rng = Pcg32::new(7, 0);
+
+        let (_, outside) = record(mat.clone());
+        let srec = mat.scatter(&ray, &outside, &mut rng).unwrap();
+        assert_eq!(srec.attenuation, Color::new(1.0, 1.0, 1.0));
+
+        // The same hit seen from inside, one unit of `t` along the ray.
+        let normal = Vec3::new(0.0, -1.0, 0.0);
+        let inside = HitRecord::new(&ray, Point3::default(), normal, 1.0, mat.clone(), 0.0, 0.0);
+        let srec = mat.scatter(&ray, &inside, &mut rng).unwrap();
+        assert!((srec.attenuation - tint).length() < 1e-9);
+    }
+
+    #[test]
+    fn tinted_glass_absorbs_inside_moved_objects() {
+        // Leaving a unit ball of glass from its center, through a translated
+        // and a rotated copy.
+        let tint = Color::new(0.25, 0.5, 1.0);
+        let mat: Arc<dyn Material> = Arc::new(Dielectric::new(1.5).with_transmittance(tint, 1.0));
+        let ball: Arc<dyn Hittable> = Arc::new(Sphere::new(Point3::default(), 1.0, mat.clone()));
+        let offset = Vec3::new(3.0, -2.0, 1.0);
+        let moved: [(Arc<dyn Hittable>, Vec3); 2] = [
+            (Arc::new(Translate::new(ball.clone(), offset)), offset),
+            (Arc::new(RotateY::new(ball, 30.0)), Vec3::zero()),
+        ];
+        let mut rng = Pcg32::new(7, 0);
+        for (obj, center) in moved {
+            let ray = Ray::new(center, Vec3::new(0.3, 0.4, -0.2));
+            let record = obj.hit(&ray, &Interval::new(0.001, INF), &mut rng).unwrap();
+            assert!(!record.front_face);
+            let srec = mat.scatter(&ray, &record, &mut rng).unwrap();
+            assert!((srec.attenuation - tint).length() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn rough_glass_conserves_energy() {
+        // Light is either reflected or transmitted, so the sampled throughput
+        // averages to a little under one from either side; the transmitted
+        // part is scaled back by eta² to undo the change in radiance.
+        let mut rng = Pcg32::new(6, 0);
+        let roughness = Box::new(SolidColor::new(Color::new(0.3, 0.3, 0.3)));
+        let mat: Arc<dyn Material> = Arc::new(RoughDielectric::new(1.5, roughness));
+        let (ray, outside) = record(mat.clone());
+        let inside = HitRecord::new(
+            &ray,
+            Point3::default(),
+            Vec3::new(0.0, -1.0, 0.0),
+            1.0,
+            mat.clone(),
+            0.0,
+            0.0,
+        );
+        for (record, eta) in [(outside, 1.5f64), (inside, 1.0 / 1.5)] {
+            let srec = mat.scatter(&ray, &record, &mut rng).unwrap();
+            let Lobe::Diffuse(pdf) = srec.lobe else {
+                panic!("rough glass should have a sampled lobe");
+            };
+            let n = 100_000;
+            let mut total = 0.0;
+            for _ in 0..n {
+                let scattered = Ray::new(record.point, pdf.generate(&mut rng));
+                if scattered.dir().near_zero() {
+                    continue;
+                }
+                let density = pdf.value(&scattered.dir(), &mut rng);
+                if density > 0.0 {
+                    let f = mat.eval(&ray, &record, &scattered, srec.attenuation).x() / density;
+                    let transmitted = scattered.dir().dot(&record.normal) < 0.0;
+                    total += if transmitted { f * eta * eta } else { f };
+                }
+            }
+            let total = total / n as f64;
+            assert!(total < 1.0 + 1e-6 && total > 0.98, "eta {}: {}", eta, total);
+        }
+    }
+
+    /// Height rising along `u`.
+    struct Ramp;
+
+    impl Texture for Ramp {
+        fn value(&self, u: f64, _v: f64, _p: &Point3) -> Color {
+            Color::new(u, u, u)
+        }
+    }
+
+    #[test]
+    fn maps_tilt_the_shading_normal() {
+        // A Lambertian shades most brightly along its normal, so the tilted
+        // normal is where the density peaks at 1 / pi. The floor's `u` runs
+        // two units along x and its normal is +y, so the ramp's slope is 3/4.
+        let tilted = Vec3::new(-0.6, 0.8, 0.0);
+        let diffuse = Arc::new(Lambertian::new_from_color(Color::new(0.5, 0.5, 0.5)));
+        // In tangent space: along u, along v, then out of the surface.
+        let texel = 0.5 * (Vec3::new(tilted.x(), 0.0, tilted.y()) + 1.0);
+        let materials: [Arc<dyn Material>; 2] = [
+            Arc::new(NormalMap::new(
+                diffuse.clone(),
+                Box::new(SolidColor::new(texel)),
+            )),
+            Arc::new(BumpMap::new(diffuse, Box::new(Ramp), 1.5)),
+        ];
+        let mut rng = Pcg32::new(9, 0);
+        for mat in materials {
+            let floor: Arc<dyn Hittable> = Arc::new(Quad::new(
+                Point3::new(-1.0, 0.0, 1.0),
+                Vec3::new(2.0, 0.0, 0.0),
+                Vec3::new(0.0, 0.0, -2.0),
+                mat.clone(),
+            ));
+            // Moved copies keep the tangents and the side that was hit; a
+            // quarter turn about y takes x to -z.
+            let quarter_turn = Vec3::new(0.0, tilted.y(), -tilted.x());
+            let floors: [(Arc<dyn Hittable>, Vec3); 3] = [
+                (floor.clone(), tilted),
+                (
+                    Arc::new(Translate::new(floor.clone(), Vec3::new(0.0, 0.5, 0.0))),
+                    tilted,
+                ),
+                (Arc::new(RotateY::new(floor, 90.0)), quarter_turn),
+            ];
+            for (floor, tilted) in floors {
+                for dir in [-1.0, 1.0] {
+                    let ray = Ray::new(Point3::new(0.1, -dir, 0.2), Vec3::new(0.0, dir, 0.0));
+                    let record = floor
+                        .hit(&ray, &Interval::new(0.001, INF), &mut rng)
+                        .unwrap();
+                    // From below, the shading normal faces down the same tilt.
+                    let expected = -dir * tilted;
+                    let along = Ray::new(record.point, expected);
+                    let pdf = mat.scattering_pdf(&ray, &record, &along);
+                    assert!((pdf - 1.0 / PI).abs() < 1e-6, "{}", pdf);
+                }
+            }
+        }
     }
 }
@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use crate::bvh::Bvh;
+use crate::triangle::{intersect, tangents};
+use crate::{
+    Aabb, BvhBuilder, BvhStats, HitRecord, Hittable, Interval, Material, Point3, Ray, Rng, Vec3,
+};
+
+/// Indices of one triangle into the vertex arrays of its `TriangleMesh`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Face {
+    pub positions: [u32; 3],
+    pub normals: Option<[u32; 3]>,
+    pub uvs: Option<[u32; 3]>,
+    pub material: u32,
+}
+
+impl Face {
+    pub fn new(positions: [u32; 3]) -> Self {
+        Self {
+            positions,
+            normals: None,
+            uvs: None,
+            material: 0,
+        }
+    }
+}
+
+/// Many triangles sharing one set of vertex arrays, with a BVH over the
+/// faces so the whole mesh is a single `Hittable`.
+pub struct TriangleMesh {
+    positions: Vec<Point3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    faces: Vec<Face>,
+    materials: Vec<Arc<dyn Material>>,
+    bvh: Bvh,
+}
+
+impl TriangleMesh {
+    /// `faces` index into `positions`, `normals`, `uvs` and `materials`.
+    /// Panics if an index is out of range.
+    pub fn new(
+        positions: Vec<Point3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<(f64, f64)>,
+        faces: Vec<Face>,
+        materials: Vec<Arc<dyn Material>>,
+    ) -> Self {
+        let in_range = |idx: &[u32; 3], len: usize| idx.iter().all(|&i| (i as usize) < len);
+        for face in &faces {
+            assert!(
+                in_range(&face.positions, positions.len()),
+                "position index out of range"
+            );
+            assert!(
+                face.normals.is_none_or(|n| in_range(&n, normals.len())),
+                "normal index out of range"
+            );
+            assert!(
+                face.uvs.is_none_or(|t| in_range(&t, uvs.len())),
+                "uv index out of range"
+            );
+            assert!(
+                (face.material as usize) < materials.len(),
+                "material index out of range"
+            );
+        }
+        let boxes: Vec<Aabb> = faces
+            .iter()
+            .map(|face| {
+                let [v0, v1, v2] = face.positions.map(|i| positions[i as usize]);
+                Aabb::new_from_aabb(Aabb::new_from_points(v0, v1), Aabb::new_from_points(v2, v2))
+                    .pad()
+            })
+            .collect();
+        Self {
+            bvh: BvhBuilder::default().build_bvh(&boxes),
+            positions,
+            normals,
+            uvs,
+            faces,
+            materials,
+        }
+    }
+
+    /// Replaces every material of the mesh with `mat`.
+    pub fn with_material(mut self, mat: Arc<dyn Material>) -> Self {
+        self.materials = vec![mat];
+        self.faces.iter_mut().for_each(|f| f.material = 0);
+        self
+    }
+
+    pub fn faces(&self) -> &[Face] {
+        &self.faces
+    }
+
+    pub fn bvh_stats(&self) -> BvhStats {
+        self.bvh.stats()
+    }
+
+    fn vertices(&self, face: &Face) -> [Point3; 3] {
+        face.positions.map(|i| self.positions[i as usize])
+    }
+
+    fn record(&self, ray: &Ray, face: &Face, t: f64, b: [f64; 3]) -> HitRecord {
+        let [v0, v1, v2] = self.vertices(face);
+        let normal = (v1 - v0).cross(&(v2 - v0)).unit_vector();
+        let uvs = face.uvs.map(|idx| idx.map(|i| self.uvs[i as usize]));
+        let (u, v) = match uvs {
+            Some([t0, t1, t2]) => (
+                b[0] * t0.0 + b[1] * t1.0 + b[2] * t2.0,
+                b[0] * t0.1 + b[1] * t1.1 + b[2] * t2.1,
+            ),
+            None => (b[1], b[2]),
+        };
+        let (tangent, bitangent) = tangents([v0, v1, v2], uvs);
+        let mut record = HitRecord::new(
+            ray,
+            ray.at(t),
+            normal,
+            t,
+            self.materials[face.material as usize].clone(),
+            u,
+            v,
+        )
+        .with_tangents(tangent, bitangent);
+        if let Some(idx) = face.normals {
+            let [n0, n1, n2] = idx.map(|i| self.normals[i as usize]);
+            let shading = (b[0] * n0 + b[1] * n1 + b[2] * n2).unit_vector();
+            record.normal = if record.front_face { shading } else { -shading };
+        }
+        record
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: &Ray, interval: &Interval, _rng: &mut dyn Rng) -> Option<HitRecord> {
+        let mut found = None;
+        self.bvh.traverse(ray, interval, |index, interval| {
+            let face = &self.faces[index];
+            let [v0, v1, v2] = self.vertices(face);
+            let hit = intersect(ray, interval, v0, v1, v2)?;
+            let t = hit.t;
+            found = Some((face, hit));
+            Some(t)
+        });
+        found.map(|(face, hit)| self.record(ray, face, hit.t, [hit.b0, hit.b1, hit.b2]))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+}
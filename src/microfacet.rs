@@ -0,0 +1,341 @@
+use crate::{Color, Onb, Pdf, Rng, Vec3, PI};
+
+/// The GGX (Trowbridge-Reitz) distribution of microfacet normals, in a
+/// shading frame whose `z` axis is the macro surface normal.
+#[derive(Debug, Clone, Copy)]
+pub struct TrowbridgeReitz {
+    alpha: f64,
+}
+
+impl TrowbridgeReitz {
+    /// Below this the surface is treated as a perfect mirror.
+    pub const SMOOTH_ALPHA: f64 = 1e-3;
+
+    pub fn new(alpha: f64) -> Self {
+        Self { alpha }
+    }
+
+    /// The usual remapping from a perceptual roughness in `[0, 1]`.
+    pub fn new_from_roughness(roughness: f64) -> Self {
+        Self::new(roughness.clamp(0.0, 1.0).powi(2))
+    }
+
+    pub fn alpha(&self) -> f64 {
+        self.alpha
+    }
+
+    pub fn is_smooth(&self) -> bool {
+        self.alpha < Self::SMOOTH_ALPHA
+    }
+
+    /// Density of microfacet normal `wm` per unit projected area.
+    pub fn d(&self, wm: &Vec3) -> f64 {
+        let a2 = self.alpha * self.alpha;
+        let cos2 = wm.z() * wm.z();
+        let denom = (a2 - 1.0) * cos2 + 1.0;
+        a2 / (PI * denom * denom)
+    }
+
+    /// Smith's auxiliary function, for the masking of direction `w`.
+    pub fn lambda(&self, w: &Vec3) -> f64 {
+        let cos2 = w.z() * w.z();
+        if cos2 <= 0.0 {
+            return 0.0;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0) / 2.0
+    }
+
+    /// Fraction of microfacets facing `w` that `w` can see.
+    pub fn g1(&self, w: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Height-correlated masking and shadowing of `wo` and `wi` together.
+    pub fn g(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Density of the normals visible from `w`, per solid angle of `wm`.
+    pub fn visible_d(&self, w: &Vec3, wm: &Vec3) -> f64 {
+        let cos = w.z().abs();
+        if cos == 0.0 {
+            return 0.0;
+        }
+        self.g1(w) * w.dot(wm).max(0.0) * self.d(wm) / cos
+    }
+
+    /// Samples a normal visible from `w`, which must be above the surface
+    /// (Heitz, "Sampling the GGX Distribution of Visible Normals").
+    pub fn sample_wm(&self, w: &Vec3, u: (f64, f64)) -> Vec3 {
+        // Stretch the view to the hemisphere configuration.
+        let vh = Vec3::new(self.alpha * w.x(), self.alpha * w.y(), w.z()).unit_vector();
+        let len2 = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if len2 > 0.0 {
+            Vec3::new(-vh.y(), vh.x(), 0.0) / len2.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(&t1);
+
+        // A point on the disk, squeezed onto the visible half.
+        let r = u.0.sqrt();
+        let phi = 2.0 * PI * u.1;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+
+        // And back to the ellipsoid.
+        Vec3::new(self.alpha * nh.x(), self.alpha * nh.y(), nh.z().max(1e-6)).unit_vector()
+    }
+
+    /// Density of `wi` reflected off a normal visible from `wo`.
+    pub fn reflection_pdf(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        if wi.z() <= 0.0 {
+            return 0.0;
+        }
+        let wm = (*wo + *wi).unit_vector();
+        let cos = wo.dot(&wm);
+        if cos <= 0.0 {
+            return 0.0;
+        }
+        self.visible_d(wo, &wm) / (4.0 * cos)
+    }
+
+    /// Reflects `wo` off a sampled visible normal. The result may point
+    /// below the surface, where `reflection_pdf` is zero.
+    pub fn sample_reflection(&self, wo: &Vec3, u: (f64, f64)) -> Vec3 {
+        (-*wo).reflect(&self.sample_wm(wo, u))
+    }
+}
+
+/// Reflectance of a conductor with complex index of refraction `eta + i k`,
+/// per channel, for light arriving at `cos_theta` to the normal.
+pub fn fresnel_complex(cos_theta: f64, eta: Color, k: Color) -> Color {
+    let channel = |eta: f64, k: f64| {
+        let cos2 = cos_theta.clamp(0.0, 1.0).powi(2);
+        let sin2 = 1.0 - cos2;
+        let t0 = eta * eta - k * k - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+        let a = ((a2_plus_b2 + t0) / 2.0).max(0.0).sqrt();
+        let cos = cos2.sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let t2 = 2.0 * a * cos;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        (rs + rp) / 2.0
+    };
+    Color::new(
+        channel(eta.x(), k.x()),
+        channel(eta.y(), k.y()),
+        channel(eta.z(), k.z()),
+    )
+}
+
+/// Reflectance of a smooth boundary between dielectrics, for light arriving
+/// at `cos_theta` to the normal from the side of lower index when
+/// `eta = n_transmitted / n_incident` is above one. Both polarizations are
+/// averaged, and total internal reflection gives one.
+pub fn fresnel_dielectric(cos_theta: f64, eta: f64) -> f64 {
+    let cos_i = cos_theta.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) / 2.0
+}
+
+/// A rough boundary between dielectrics, reflecting and transmitting
+/// through GGX microfacets. Works in a shading frame where `wo` is above the
+/// surface and `eta` is the index below over the index above.
+#[derive(Debug, Clone, Copy)]
+pub struct GgxDielectric {
+    pub distribution: TrowbridgeReitz,
+    pub eta: f64,
+}
+
+impl GgxDielectric {
+    /// The microfacet normal that takes `wo` to `wi`, if it faces both.
+    fn half_vector(&self, wo: &Vec3, wi: &Vec3) -> Option<Vec3> {
+        let reflect = wi.z() > 0.0;
+        let eta = if reflect { 1.0 } else { self.eta };
+        let wm = *wi * eta + *wo;
+        if wo.z() <= 0.0 || wi.z().abs() < 1e-9 || wm.near_zero() {
+            return None;
+        }
+        let wm = wm.unit_vector();
+        let wm = if wm.z() < 0.0 { -wm } else { wm };
+        // Microfacets seen from behind by either direction don't count.
+        if wm.dot(wi) * wi.z() < 0.0 || wm.dot(wo) < 0.0 {
+            return None;
+        }
+        Some(wm)
+    }
+
+    /// The BSDF times `|cos|` of `wi`, for radiance travelling from `wi` to
+    /// `wo`.
+    pub fn f(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        let Some(wm) = self.half_vector(wo, wi) else {
+            return 0.0;
+        };
+        let reflectance = fresnel_dielectric(wo.dot(&wm), self.eta);
+        let dg = self.distribution.d(&wm) * self.distribution.g(wo, wi);
+        if wi.z() > 0.0 {
+            dg * reflectance / (4.0 * wo.z())
+        } else {
+            let denom = wi.dot(&wm) + wo.dot(&wm) / self.eta;
+            // Radiance is compressed into the smaller solid angle on the
+            // denser side, hence the final 1 / eta².
+            dg * (1.0 - reflectance) * (wi.dot(&wm) * wo.dot(&wm)).abs()
+                / (wo.z() * denom * denom * self.eta * self.eta)
+        }
+    }
+
+    /// Density with which `sample` picks `wi`.
+    pub fn pdf(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        let Some(wm) = self.half_vector(wo, wi) else {
+            return 0.0;
+        };
+        let reflectance = fresnel_dielectric(wo.dot(&wm), self.eta);
+        let visible = self.distribution.visible_d(wo, &wm);
+        if wi.z() > 0.0 {
+            reflectance * visible / (4.0 * wo.dot(&wm))
+        } else {
+            let denom = wi.dot(&wm) + wo.dot(&wm) / self.eta;
+            (1.0 - reflectance) * visible * wi.dot(&wm).abs() / (denom * denom)
+        }
+    }
+
+    /// Picks a visible microfacet, then reflects or refracts through it in
+    /// proportion to its Fresnel reflectance.
+    pub fn sample(&self, wo: &Vec3, u: (f64, f64), uc: f64) -> Option<Vec3> {
+        let wm = self.distribution.sample_wm(wo, u);
+        if uc < fresnel_dielectric(wo.dot(&wm), self.eta) {
+            let wi = (-*wo).reflect(&wm);
+            (wi.z() > 0.0).then_some(wi)
+        } else {
+            let wi = (-*wo).refract(&wm, 1.0 / self.eta).unit_vector();
+            (wi.z() < 0.0).then_some(wi)
+        }
+    }
+}
+
+/// Directions reflected off GGX normals visible from `wo`.
+pub struct GgxReflectionPdf {
+    uvw: Onb,
+    wo: Vec3,
+    distribution: TrowbridgeReitz,
+}
+
+impl GgxReflectionPdf {
+    /// `wo` points away from the surface, in world space.
+    pub fn new(normal: &Vec3, wo: &Vec3, distribution: TrowbridgeReitz) -> Self {
+        let uvw = Onb::new(normal);
+        let wo = uvw.to_local(&wo.unit_vector());
+        Self {
+            uvw,
+            wo,
+            distribution,
+        }
+    }
+}
+
+impl Pdf for GgxReflectionPdf {
+    fn value(&self, direction: &Vec3, _rng: &mut dyn Rng) -> f64 {
+        let wi = self.uvw.to_local(&direction.unit_vector());
+        self.distribution.reflection_pdf(&self.wo, &wi)
+    }
+
+    fn generate(&self, rng: &mut dyn Rng) -> Vec3 {
+        let u = (rng.next_f64(), rng.next_f64());
+        self.uvw
+            .transform(&self.distribution.sample_reflection(&self.wo, u))
+    }
+}
+
+/// Directions reflected or transmitted by a rough dielectric, seen from
+/// `wo`.
+pub struct GgxDielectricPdf {
+    uvw: Onb,
+    wo: Vec3,
+    bsdf: GgxDielectric,
+}
+
+impl GgxDielectricPdf {
+    /// `normal` and `wo` are on the same side, in world space.
+    pub fn new(normal: &Vec3, wo: &Vec3, bsdf: GgxDielectric) -> Self {
+        let uvw = Onb::new(normal);
+        let wo = uvw.to_local(&wo.unit_vector());
+        Self { uvw, wo, bsdf }
+    }
+}
+
+impl Pdf for GgxDielectricPdf {
+    fn value(&self, direction: &Vec3, _rng: &mut dyn Rng) -> f64 {
+        let wi = self.uvw.to_local(&direction.unit_vector());
+        self.bsdf.pdf(&self.wo, &wi)
+    }
+
+    fn generate(&self, rng: &mut dyn Rng) -> Vec3 {
+        let u = (rng.next_f64(), rng.next_f64());
+        let uc = rng.next_f64();
+        self.bsdf
+            .sample(&self.wo, u, uc)
+            .map_or(Vec3::zero(), |wi| self.uvw.transform(&wi))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fresnel_complex, TrowbridgeReitz};
+    use crate::{Color, Pcg32, Rng, Vec3, PI};
+
+    #[test]
+    fn normals_project_to_unit_area() {
+        // The projected area of all microfacets is that of the surface. The
+        // distribution is isotropic, so this integrates over cos² of the
+        // normal's angle, in which the solid angle is uniform.
+        for alpha in [0.05, 0.3, 1.0] {
+            let ggx = TrowbridgeReitz::new(alpha);
+            let n = 1_000_000;
+            let area: f64 = (0..n)
+                .map(|i| {
+                    let cos = ((i as f64 + 0.5) / n as f64).sqrt();
+                    let sin = (1.0 - cos * cos).sqrt();
+                    ggx.d(&Vec3::new(sin, 0.0, cos)) * PI
+                })
+                .sum::<f64>()
+                / n as f64;
+            assert!((area - 1.0).abs() < 1e-3, "alpha {}: {}", alpha, area);
+        }
+    }
+
+    #[test]
+    fn visible_normals_face_the_viewer() {
+        let ggx = TrowbridgeReitz::new(0.5);
+        let wo = Vec3::new(0.6, 0.0, 0.8);
+        let mut rng = Pcg32::new(2, 0);
+        for _ in 0..1000 {
+            let wm = ggx.sample_wm(&wo, (rng.next_f64(), rng.next_f64()));
+            assert!((wm.length() - 1.0).abs() < 1e-9);
+            assert!(wm.z() > 0.0 && wo.dot(&wm) >= 0.0);
+        }
+    }
+
+    #[test]
+    fn fresnel_limits() {
+        // A dielectric (k = 0) matches the familiar 4% of glass at normal
+        // incidence, and everything reflects at grazing angles.
+        let glass = Color::new(1.5, 1.5, 1.5);
+        let f = fresnel_complex(1.0, glass, Color::zero());
+        assert!((f.x() - 0.04).abs() < 1e-9);
+        let f = fresnel_complex(0.0, glass, Color::new(3.0, 3.0, 3.0));
+        assert!((f.x() - 1.0).abs() < 1e-9);
+    }
+}
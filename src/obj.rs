@@ -0,0 +1,405 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::mesh::Face;
+use crate::{
+    Color, Dielectric, DiffuseLight, Error, ImageTexture, Lambertian, Material, Metal, Point3,
+    Principled, Result, SolidColor, Texture, TriangleMesh, Vec3,
+};
+
+/// Loads a Wavefront OBJ file, and the MTL libraries it references, as one
+/// `TriangleMesh`. Polygons are split into triangle fans. Faces with vertex
+/// normals are shaded smoothly.
+///
+/// MTL materials using the PBR extension (`Pr`, `Pm`, `Ps` or `Pc`) become
+/// `Principled`, with `Kd` or `map_Kd` as the base color, `Ke` as emission,
+/// `1 - d` as transmission and `Ni` as the index. Others are mapped onto the
+/// closest built-in material: an emissive `Ke` gives `DiffuseLight`,
+/// transparency (`d < 1`, `Tr > 0` or a refractive `illum`) gives
+/// `Dielectric` with `Ni`, a specular `Ks` stronger than `Kd` gives `Metal`
+/// with a fuzz derived from `Ns`, and anything else is `Lambertian` with `Kd`
+/// or `map_Kd`.
+///
+/// Also returns warnings about things that were skipped rather than treated
+/// as errors, such as a `usemtl` naming an unknown material.
+pub fn load_obj(path: impl AsRef<Path>) -> Result<(TriangleMesh, Vec<String>)> {
+    let path = path.as_ref();
+    let text = read(path)?;
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    let mut warnings = Vec::new();
+    let mesh = parse_obj(
+        &text,
+        path,
+        &mut |lib| {
+            let lib_path = base_dir.join(lib);
+            let text = read(&lib_path)?;
+            parse_mtl(&text, &lib_path, base_dir)
+        },
+        &mut warnings,
+    )?;
+    Ok((mesh, warnings))
+}
+
+fn read(path: &Path) -> Result<String> {
+    fs::read_to_string(path).map_err(|source| Error::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+type MtlLoader<'a> = dyn FnMut(&str) -> Result<HashMap<String, Arc<dyn Material>>> + 'a;
+
+fn syntax(path: &Path, line: usize, message: impl Into<String>) -> Error {
+    Error::ObjSyntax {
+        path: path.to_path_buf(),
+        line,
+        message: message.into(),
+    }
+}
+
+fn parse_floats<const N: usize>(args: &[&str], path: &Path, line: usize) -> Result<[f64; N]> {
+    let mut out = [0.0; N];
+    if args.len() < N {
+        return Err(syntax(path, line, format!("expected {} numbers", N)));
+    }
+    for (o, a) in out.iter_mut().zip(args) {
+        *o = a
+            .parse()
+            .map_err(|_| syntax(path, line, format!("invalid number `{}`", a)))?;
+    }
+    Ok(out)
+}
+
+/// Resolves a 1-based, possibly negative OBJ index against `len` elements.
+fn resolve(token: &str, len: usize, path: &Path, line: usize) -> Result<u32> {
+    let i: i64 = token
+        .parse()
+        .map_err(|_| syntax(path, line, format!("invalid index `{}`", token)))?;
+    let idx = if i > 0 { i - 1 } else { len as i64 + i };
+    if i == 0 || idx < 0 || idx >= len as i64 {
+        return Err(syntax(path, line, format!("index {} out of range", i)));
+    }
+    Ok(idx as u32)
+}
+
+fn parse_obj(
+    text: &str,
+    path: &Path,
+    load_mtl: &mut MtlLoader,
+    warnings: &mut Vec<String>,
+) -> Result<TriangleMesh> {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut faces = Vec::new();
+
+    let default: Arc<dyn Material> =
+        Arc::new(Lambertian::new_from_color(Color::new(0.73, 0.73, 0.73)));
+    let mut materials = vec![default];
+    let mut library: HashMap<String, Arc<dyn Material>> = HashMap::new();
+    let mut material_ids: HashMap<String, u32> = HashMap::new();
+    let mut current = 0;
+
+    for (number, line) in text.lines().enumerate() {
+        let number = number + 1;
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut parts = line.split_whitespace();
+        let Some(keyword) = parts.next() else {
+            continue;
+        };
+        let args: Vec<&str> = parts.collect();
+        match keyword {
+            "v" => {
+                let [x, y, z] = parse_floats(&args, path, number)?;
+                positions.push(Point3::new(x, y, z));
+            }
+            "vn" => {
+                let [x, y, z] = parse_floats(&args, path, number)?;
+                normals.push(Vec3::new(x, y, z).unit_vector());
+            }
+            "vt" => {
+                let [u, v] = parse_floats(&args, path, number)?;
+                uvs.push((u, v));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(syntax(path, number, "a face needs at least 3 vertices"));
+                }
+                let mut corners = Vec::with_capacity(args.len());
+                for arg in &args {
+                    let mut idx = arg.split('/');
+                    let v = resolve(idx.next().unwrap_or(""), positions.len(), path, number)?;
+                    let vt = match idx.next() {
+                        Some(t) if !t.is_empty() => Some(resolve(t, uvs.len(), path, number)?),
+                        _ => None,
+                    };
+                    let vn = match idx.next() {
+                        Some(n) if !n.is_empty() => Some(resolve(n, normals.len(), path, number)?),
+                        _ => None,
+                    };
+                    corners.push((v, vt, vn));
+                }
+                for i in 1..corners.len() - 1 {
+                    let [a, b, c] = [corners[0], corners[i], corners[i + 1]];
+                    faces.push(Face {
+                        positions: [a.0, b.0, c.0],
+                        uvs: a.1.zip(b.1).zip(c.1).map(|((a, b), c)| [a, b, c]),
+                        normals: a.2.zip(b.2).zip(c.2).map(|((a, b), c)| [a, b, c]),
+                        material: current,
+                    });
+                }
+            }
+            "mtllib" => {
+                for lib in &args {
+                    library.extend(load_mtl(lib)?);
+                }
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                current = match (material_ids.get(&name), library.get(&name)) {
+                    (Some(&id), _) => id,
+                    (None, Some(mat)) => {
+                        materials.push(mat.clone());
+                        let id = materials.len() as u32 - 1;
+                        material_ids.insert(name, id);
+                        id
+                    }
+                    (None, None) => {
+                        warnings.push(format!(
+                            "{}:{}: unknown material `{}`, using the default",
+                            path.display(),
+                            number,
+                            name
+                        ));
+                        0
+                    }
+                };
+            }
+            // Groups, objects, smoothing groups, lines and points don't change
+            // the surface, and other keywords aren't supported.
+            _ => {}
+        }
+    }
+    Ok(TriangleMesh::new(positions, normals, uvs, faces, materials))
+}
+
+#[derive(Default)]
+struct MtlDef {
+    kd: Option<Color>,
+    ks: Option<Color>,
+    ke: Option<Color>,
+    ns: Option<f64>,
+    ni: Option<f64>,
+    dissolve: Option<f64>,
+    illum: Option<u32>,
+    map_kd: Option<PathBuf>,
+    roughness: Option<f64>,
+    metallic: Option<f64>,
+    sheen: Option<f64>,
+    clearcoat: Option<f64>,
+}
+
+impl MtlDef {
+    fn build(&self, base_dir: &Path) -> Result<Arc<dyn Material>> {
+        let pbr = [self.roughness, self.metallic, self.sheen, self.clearcoat];
+        if pbr.iter().any(Option::is_some) {
+            return self.build_principled(base_dir);
+        }
+        let max = |c: Option<Color>| c.map_or(0.0, |c| c.x().max(c.y()).max(c.z()));
+        if max(self.ke) > 0.0 {
+            return Ok(Arc::new(DiffuseLight::new_from_color(
+                self.ke.unwrap_or_default(),
+            )));
+        }
+        let transparent =
+            self.dissolve.is_some_and(|d| d < 1.0) || matches!(self.illum, Some(4 | 6 | 7 | 9));
+        if transparent {
+            return Ok(Arc::new(Dielectric::new(self.ni.unwrap_or(1.5))));
+        }
+        if self.map_kd.is_none() && max(self.ks) > max(self.kd) {
+            // Phong exponents run from ~1 (rough) to ~1000 (mirror).
+            let fuzz = (2.0 / (self.ns.unwrap_or(0.0) + 2.0))
+                .sqrt()
+                .clamp(0.0, 1.0);
+            return Ok(Arc::new(Metal::new(self.ks.unwrap_or_default(), fuzz)));
+        }
+        Ok(match &self.map_kd {
+            Some(map) => Arc::new(Lambertian::new(Box::new(ImageTexture::new(
+                base_dir.join(map),
+            )?))),
+            None => Arc::new(Lambertian::new_from_color(
+                self.kd.unwrap_or(Color::new(0.73, 0.73, 0.73)),
+            )),
+        })
+    }
+
+    fn build_principled(&self, base_dir: &Path) -> Result<Arc<dyn Material>> {
+        let gray = |x: f64| -> Box<dyn Texture> { Box::new(SolidColor::new(Color::new(x, x, x))) };
+        let mut mat = match &self.map_kd {
+            Some(map) => Principled::new(Box::new(ImageTexture::new(base_dir.join(map))?)),
+            None => Principled::new_from_color(self.kd.unwrap_or(Color::new(0.73, 0.73, 0.73))),
+        }
+        .with_ir(self.ni.unwrap_or(1.5));
+        if let Some(roughness) = self.roughness {
+            mat = mat.with_roughness(gray(roughness));
+        }
+        if let Some(metallic) = self.metallic {
+            mat = mat.with_metallic(gray(metallic));
+        }
+        if let Some(sheen) = self.sheen {
+            mat = mat.with_sheen(gray(sheen));
+        }
+        if let Some(clearcoat) = self.clearcoat {
+            mat = mat.with_clearcoat(gray(clearcoat));
+        }
+        if let Some(dissolve) = self.dissolve {
+            mat = mat.with_transmission(gray(1.0 - dissolve));
+        }
+        if let Some(ke) = self.ke {
+            mat = mat.with_emission(Box::new(SolidColor::new(ke)));
+        }
+        Ok(Arc::new(mat))
+    }
+}
+
+fn parse_mtl(
+    text: &str,
+    path: &Path,
+    base_dir: &Path,
+) -> Result<HashMap<String, Arc<dyn Material>>> {
+    let mut defs: Vec<(String, MtlDef)> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let number = number + 1;
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut parts = line.split_whitespace();
+        let Some(keyword) = parts.next() else {
+            continue;
+        };
+        let args: Vec<&str> = parts.collect();
+        if keyword == "newmtl" {
+            defs.push((args.join(" "), MtlDef::default()));
+            continue;
+        }
+        let Some((_, def)) = defs.last_mut() else {
+            return Err(syntax(path, number, "statement before `newmtl`"));
+        };
+        let color = |args: &[&str]| {
+            parse_floats::<3>(args, path, number).map(|[r, g, b]| Some(Color::new(r, g, b)))
+        };
+        let scalar = |args: &[&str]| parse_floats::<1>(args, path, number).map(|[x]| Some(x));
+        match keyword {
+            "Kd" => def.kd = color(&args)?,
+            "Ks" => def.ks = color(&args)?,
+            "Ke" => def.ke = color(&args)?,
+            "Ns" => def.ns = scalar(&args)?,
+            "Ni" => def.ni = scalar(&args)?,
+            "d" => def.dissolve = scalar(&args)?,
+            "Tr" => def.dissolve = scalar(&args)?.map(|tr| 1.0 - tr),
+            "illum" => def.illum = scalar(&args)?.map(|i| i as u32),
+            "Pr" => def.roughness = scalar(&args)?,
+            "Pm" => def.metallic = scalar(&args)?,
+            "Ps" => def.sheen = scalar(&args)?,
+            "Pc" => def.clearcoat = scalar(&args)?,
+            // Options such as `-s 1 1 1` come before the file name.
+            "map_Kd" => def.map_kd = args.last().map(PathBuf::from),
+            _ => {}
+        }
+    }
+    defs.into_iter()
+        .map(|(name, def)| Ok((name, def.build(base_dir)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    use super::{parse_mtl, parse_obj};
+    use crate::{Error, Hittable, Interval, Pcg32, Point3, Ray, Vec3, INF};
+
+    const MTL: &str = "
+        newmtl lamp
+        Ke 4 4 4
+
+        newmtl glass
+        Ni 1.45
+        d 0.2
+
+        newmtl red
+        Kd 0.8 0.1 0.1
+
+        newmtl brushed
+        Kd 0.9 0.9 0.9
+        Pm 1
+        Pr 0.3
+    ";
+
+    #[test]
+    fn quad_with_normals_and_materials() {
+        let obj = "
+            mtllib scene.mtl
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            vt 0 0
+            vt 1 0
+            vt 1 1
+            vt 0 1
+            vn 0 0 1
+            usemtl red
+            f 1/1/1 2/2/1 3/3/1 4/4/1
+            usemtl glass
+            f -4 -2 -1
+            usemtl chrome
+        ";
+        let mut loads = 0;
+        let mut warnings = Vec::new();
+        let mesh = parse_obj(
+            obj,
+            Path::new("test.obj"),
+            &mut |lib| {
+                loads += 1;
+                assert_eq!(lib, "scene.mtl");
+                parse_mtl(MTL, Path::new("scene.mtl"), Path::new(""))
+            },
+            &mut warnings,
+        )
+        .unwrap();
+        assert_eq!(loads, 1);
+        assert_eq!(
+            warnings,
+            ["test.obj:16: unknown material `chrome`, using the default"]
+        );
+        assert_eq!(mesh.faces().len(), 3);
+        assert!(mesh.faces()[0].normals.is_some());
+        assert!(mesh.faces()[2].normals.is_none());
+        assert_ne!(mesh.faces()[0].material, mesh.faces()[2].material);
+
+        let ray = Ray::new(Point3::new(0.75, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rng = Pcg32::new(0, 0);
+        let rec = mesh
+            .hit(&ray, &Interval::new(0.001, INF), &mut rng)
+            .unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-12);
+        assert!((rec.u - 0.75).abs() < 1e-12);
+        assert!((rec.v - 0.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn bad_index() {
+        let obj = "v 0 0 0\nv 1 0 0\nf 1 2 3\n";
+        let err = parse_obj(
+            obj,
+            Path::new("bad.obj"),
+            &mut |_| Ok(HashMap::new()),
+            &mut Vec::new(),
+        )
+        .err()
+        .unwrap();
+        assert!(matches!(err, Error::ObjSyntax { line: 3, .. }));
+    }
+}
@@ -0,0 +1,47 @@
+use crate::Vec3;
+
+/// An orthonormal basis whose `w` axis is a given direction.
+#[derive(Debug, Clone, Copy)]
+pub struct Onb {
+    axis: [Vec3; 3],
+}
+
+impl Onb {
+    pub fn new(w: &Vec3) -> Self {
+        let w = w.unit_vector();
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = w.cross(&a).unit_vector();
+        let u = w.cross(&v);
+        Self { axis: [u, v, w] }
+    }
+
+    pub fn u(&self) -> Vec3 {
+        self.axis[0]
+    }
+
+    pub fn v(&self) -> Vec3 {
+        self.axis[1]
+    }
+
+    pub fn w(&self) -> Vec3 {
+        self.axis[2]
+    }
+
+    /// Takes `local`, given in this basis, to world coordinates.
+    pub fn transform(&self, local: &Vec3) -> Vec3 {
+        local.x() * self.u() + local.y() * self.v() + local.z() * self.w()
+    }
+
+    /// Takes `world` to coordinates in this basis.
+    pub fn to_local(&self, world: &Vec3) -> Vec3 {
+        Vec3::new(
+            world.dot(&self.u()),
+            world.dot(&self.v()),
+            world.dot(&self.w()),
+        )
+    }
+}
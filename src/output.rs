@@ -0,0 +1,350 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Stdout, Write};
+use std::path::{Path, PathBuf};
+
+use exr::image::{Layer, RgbChannels, SpecificChannels};
+use exr::meta::attribute::IntegerBounds;
+use exr::meta::header::{ImageAttributes, LayerAttributes};
+use exr::prelude::{Encoding, GetPixel, Vec2, WritableImage};
+use image::codecs::hdr::HdrEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::{ExtendedColorType, ImageBuffer, ImageFormat, Rgb};
+
+use crate::{color_to_rgb16, color_to_rgb8, write_color, Error, Framebuffer, Result};
+
+/// Somewhere a finished render can be sent.
+pub trait Sink {
+    fn write(&mut self, framebuffer: &Framebuffer) -> Result<()>;
+}
+
+/// Writes ASCII P3 PPM to any `Write`.
+pub struct PpmSink<W: Write> {
+    out: W,
+}
+
+impl<W: Write> PpmSink<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+}
+
+impl PpmSink<Stdout> {
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl<W: Write> Sink for PpmSink<W> {
+    fn write(&mut self, framebuffer: &Framebuffer) -> Result<()> {
+        self.write_ppm(framebuffer).map_err(Error::Stream)
+    }
+}
+
+impl<W: Write> PpmSink<W> {
+    fn write_ppm(&mut self, framebuffer: &Framebuffer) -> io::Result<()> {
+        let mut out = BufWriter::new(&mut self.out);
+        writeln!(out, "P3")?;
+        writeln!(out, "{} {}", framebuffer.width(), framebuffer.height())?;
+        writeln!(out, "255")?;
+        for pixel_color in framebuffer.pixels() {
+            write_color(&mut out, pixel_color)?;
+        }
+        out.flush()
+    }
+}
+
+/// Encodings understood by `FileSink`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// 8-bit sRGB PNG.
+    Png,
+    /// 16-bit PNG, same gamma as `Png`.
+    Png16,
+    /// Baseline JPEG, `quality` in 1..=100.
+    Jpeg { quality: u8 },
+    /// Binary P6 PPM.
+    Ppm,
+    /// Linear 32-bit float OpenEXR.
+    Exr,
+    /// Radiance RGBE.
+    Hdr,
+    /// Portable float map, linear 32-bit float.
+    Pfm,
+}
+
+impl Format {
+    pub const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+    /// Picks a format from the extension of `path`, case-insensitively.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let ext = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpeg {
+                quality: Self::DEFAULT_JPEG_QUALITY,
+            }),
+            "ppm" => Some(Self::Ppm),
+            "exr" => Some(Self::Exr),
+            "hdr" => Some(Self::Hdr),
+            "pfm" => Some(Self::Pfm),
+            _ => None,
+        }
+    }
+
+    /// The 16-bit variant of `Png`, or `None` for other formats.
+    pub fn with_png16(self) -> Option<Self> {
+        matches!(self, Self::Png | Self::Png16).then_some(Self::Png16)
+    }
+
+    /// `Jpeg` at another quality, or `None` for other formats.
+    pub fn with_jpeg_quality(self, quality: u8) -> Option<Self> {
+        matches!(self, Self::Jpeg { .. }).then_some(Self::Jpeg { quality })
+    }
+}
+
+/// Encodes the framebuffer into a file.
+pub struct FileSink {
+    path: PathBuf,
+    format: Format,
+}
+
+impl FileSink {
+    /// Creates a sink whose format follows the file extension.
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let format =
+            Format::from_path(path).ok_or_else(|| Error::UnsupportedFormat(path.to_path_buf()))?;
+        Ok(Self::new_with_format(path, format))
+    }
+
+    pub fn new_with_format(path: impl AsRef<Path>, format: Format) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            format,
+        }
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    fn rgb8(framebuffer: &Framebuffer) -> Vec<u8> {
+        framebuffer
+            .pixels()
+            .iter()
+            .flat_map(color_to_rgb8)
+            .collect()
+    }
+}
+
+impl Sink for FileSink {
+    fn write(&mut self, framebuffer: &Framebuffer) -> Result<()> {
+        let width = framebuffer.width() as u32;
+        let height = framebuffer.height() as u32;
+        let image_err = |source| Error::Image {
+            path: self.path.clone(),
+            source,
+        };
+        match self.format {
+            Format::Png => image::save_buffer_with_format(
+                &self.path,
+                &Self::rgb8(framebuffer),
+                width,
+                height,
+                ExtendedColorType::Rgb8,
+                ImageFormat::Png,
+            )
+            .map_err(image_err),
+            Format::Png16 => {
+                let data = framebuffer
+                    .pixels()
+                    .iter()
+                    .flat_map(color_to_rgb16)
+                    .collect();
+                let buffer: ImageBuffer<Rgb<u16>, Vec<u16>> =
+                    ImageBuffer::from_raw(width, height, data)
+                        .expect("framebuffer size matches its pixels");
+                buffer
+                    .save_with_format(&self.path, ImageFormat::Png)
+                    .map_err(image_err)
+            }
+            Format::Jpeg { quality } => {
+                let mut out = self.create()?;
+                JpegEncoder::new_with_quality(&mut out, quality.clamp(1, 100))
+                    .encode(
+                        &Self::rgb8(framebuffer),
+                        width,
+                        height,
+                        ExtendedColorType::Rgb8,
+                    )
+                    .map_err(image_err)?;
+                out.flush().map_err(|source| self.io_err(source))
+            }
+            Format::Ppm => {
+                let mut out = self.create()?;
+                write!(out, "P6\n{} {}\n255\n", width, height)
+                    .and_then(|_| out.write_all(&Self::rgb8(framebuffer)))
+                    .and_then(|_| out.flush())
+                    .map_err(|source| self.io_err(source))
+            }
+            Format::Exr => write_exr_layers(&self.path, &[("", framebuffer)]),
+            Format::Hdr => {
+                let data: Vec<Rgb<f32>> = framebuffer
+                    .pixels()
+                    .iter()
+                    .map(|c| Rgb([c.x() as f32, c.y() as f32, c.z() as f32]))
+                    .collect();
+                HdrEncoder::new(self.create()?)
+                    .encode(&data, width as usize, height as usize)
+                    .map_err(image_err)
+            }
+            Format::Pfm => {
+                let mut out = self.create()?;
+                Self::write_pfm(&mut out, framebuffer).map_err(|source| self.io_err(source))
+            }
+        }
+    }
+}
+
+impl FileSink {
+    fn create(&self) -> Result<BufWriter<File>> {
+        File::create(&self.path)
+            .map(BufWriter::new)
+            .map_err(|source| self.io_err(source))
+    }
+
+    fn io_err(&self, source: io::Error) -> Error {
+        Error::Io {
+            path: self.path.clone(),
+            source,
+        }
+    }
+
+    fn write_pfm(out: &mut impl Write, framebuffer: &Framebuffer) -> io::Result<()> {
+        // A negative scale marks little-endian samples. Rows run bottom to top.
+        write!(
+            out,
+            "PF\n{} {}\n-1.0\n",
+            framebuffer.width(),
+            framebuffer.height()
+        )?;
+        for row in framebuffer.rows().collect::<Vec<_>>().into_iter().rev() {
+            for c in row {
+                for channel in [c.x(), c.y(), c.z()] {
+                    out.write_all(&(channel as f32).to_le_bytes())?;
+                }
+            }
+        }
+        out.flush()
+    }
+}
+
+/// Writes several framebuffers of the same size as named layers of one
+/// OpenEXR file, e.g. a beauty pass next to its AOVs. An empty name writes
+/// plain `R`, `G`, `B` channels, which most viewers show by default.
+pub fn write_exr_layers(path: impl AsRef<Path>, layers: &[(&str, &Framebuffer)]) -> Result<()> {
+    let path = path.as_ref();
+    let Some((_, first)) = layers.first() else {
+        return Err(Error::InvalidLayers("no layers to write"));
+    };
+    let size = Vec2(first.width(), first.height());
+    if layers
+        .iter()
+        .any(|(_, fb)| fb.width() != size.0 || fb.height() != size.1)
+    {
+        return Err(Error::InvalidLayers("all layers must have the same size"));
+    }
+
+    let layers: Vec<_> = layers
+        .iter()
+        .map(|(name, fb)| exr_layer(name, fb))
+        .collect();
+    exr::image::Image::from_layers(
+        ImageAttributes::new(IntegerBounds::from_dimensions(size)),
+        layers,
+    )
+    .write()
+    .to_file(path)
+    .map_err(|source| Error::Exr {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+struct ExrPixels<'a>(&'a Framebuffer);
+
+impl GetPixel for ExrPixels<'_> {
+    type Pixel = (f32, f32, f32);
+
+    fn get_pixel(&self, pos: Vec2<usize>) -> Self::Pixel {
+        let c = self.0.get(pos.0, pos.1);
+        (c.x() as f32, c.y() as f32, c.z() as f32)
+    }
+}
+
+fn exr_layer<'a>(
+    name: &str,
+    framebuffer: &'a Framebuffer,
+) -> Layer<SpecificChannels<ExrPixels<'a>, RgbChannels>> {
+    let attributes = if name.is_empty() {
+        LayerAttributes::default()
+    } else {
+        LayerAttributes::named(name)
+    };
+    Layer::new(
+        Vec2(framebuffer.width(), framebuffer.height()),
+        attributes,
+        Encoding::FAST_LOSSLESS,
+        SpecificChannels::rgb(ExrPixels(framebuffer)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileSink, Format, Sink};
+    use crate::{Color, Framebuffer};
+
+    #[test]
+    fn format_from_extension() {
+        assert_eq!(Format::from_path("out.png"), Some(Format::Png));
+        assert_eq!(Format::from_path("out.PPM"), Some(Format::Ppm));
+        assert_eq!(
+            Format::from_path("dir/out.jpeg"),
+            Some(Format::Jpeg {
+                quality: Format::DEFAULT_JPEG_QUALITY
+            })
+        );
+        assert_eq!(Format::from_path("out.txt"), None);
+        assert_eq!(Format::from_path("out"), None);
+    }
+
+    #[test]
+    fn write_every_format() {
+        let mut framebuffer = Framebuffer::new(4, 2);
+        framebuffer.set(1, 0, Color::new(15.0, 0.5, 0.25));
+        let dir = std::env::temp_dir().join(format!("rtracer-output-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["a.png", "a.jpg", "a.ppm", "a.exr", "a.hdr", "a.pfm"] {
+            let path = dir.join(name);
+            FileSink::new(&path).unwrap().write(&framebuffer).unwrap();
+            if name != "a.pfm" {
+                let decoded = image::open(&path).unwrap();
+                assert_eq!((decoded.width(), decoded.height()), (4, 2), "{}", name);
+            }
+        }
+        let png16 = dir.join("a16.png");
+        let format = Format::from_path(&png16).unwrap().with_png16().unwrap();
+        FileSink::new_with_format(&png16, format)
+            .write(&framebuffer)
+            .unwrap();
+        let decoded = image::open(&png16).unwrap();
+        assert_eq!(decoded.color(), image::ColorType::Rgb16);
+        assert_eq!((decoded.width(), decoded.height()), (4, 2));
+        let jpeg = Format::from_path("a.jpg").unwrap().with_jpeg_quality(40);
+        assert_eq!(jpeg, Some(Format::Jpeg { quality: 40 }));
+        assert_eq!(Format::Exr.with_png16(), None);
+        let exr = image::open(dir.join("a.exr")).unwrap().into_rgb32f();
+        assert_eq!(exr.get_pixel(1, 0)[0], 15.0);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
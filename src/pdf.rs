@@ -0,0 +1,156 @@
+use crate::{Hittable, Onb, Point3, Rng, Vec3, PI};
+
+/// A probability density over directions that can also be sampled.
+pub trait Pdf {
+    fn value(&self, direction: &Vec3, rng: &mut dyn Rng) -> f64;
+    /// A random direction, or `Vec3::zero()` when sampling failed. Callers
+    /// drop zero directions before asking for their `value`.
+    fn generate(&self, rng: &mut dyn Rng) -> Vec3;
+}
+
+/// Weight for a sample drawn with density `pdf` when another strategy could
+/// have drawn the same direction with density `other`.
+pub fn power_heuristic(pdf: f64, other: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other * other);
+    if a + b > 0.0 {
+        a / (a + b)
+    } else {
+        0.0
+    }
+}
+
+/// Uniform over the whole sphere of directions.
+pub struct SpherePdf;
+
+impl Pdf for SpherePdf {
+    fn value(&self, _direction: &Vec3, _rng: &mut dyn Rng) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn generate(&self, rng: &mut dyn Rng) -> Vec3 {
+        Vec3::random_unit_vector(rng)
+    }
+}
+
+/// Cosine-weighted over the hemisphere about a normal.
+pub struct CosinePdf {
+    uvw: Onb,
+}
+
+impl CosinePdf {
+    pub fn new(normal: &Vec3) -> Self {
+        Self {
+            uvw: Onb::new(normal),
+        }
+    }
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, direction: &Vec3, _rng: &mut dyn Rng) -> f64 {
+        let cos_theta = direction.unit_vector().dot(&self.uvw.w());
+        f64::max(0.0, cos_theta / PI)
+    }
+
+    fn generate(&self, rng: &mut dyn Rng) -> Vec3 {
+        self.uvw.transform(&Vec3::random_cosine_direction(rng))
+    }
+}
+
+/// Towards the surface of `objects`, as seen from `origin`.
+pub struct HittablePdf<'a> {
+    objects: &'a dyn Hittable,
+    origin: Point3,
+}
+
+impl<'a> HittablePdf<'a> {
+    pub fn new(objects: &'a dyn Hittable, origin: Point3) -> Self {
+        Self { objects, origin }
+    }
+}
+
+impl Pdf for HittablePdf<'_> {
+    fn value(&self, direction: &Vec3, rng: &mut dyn Rng) -> f64 {
+        self.objects.pdf_value(&self.origin, direction, rng)
+    }
+
+    fn generate(&self, rng: &mut dyn Rng) -> Vec3 {
+        self.objects.random(&self.origin, rng)
+    }
+}
+
+/// An even blend of two densities.
+pub struct MixturePdf<'a> {
+    p: [&'a dyn Pdf; 2],
+}
+
+impl<'a> MixturePdf<'a> {
+    pub fn new(p0: &'a dyn Pdf, p1: &'a dyn Pdf) -> Self {
+        Self { p: [p0, p1] }
+    }
+}
+
+impl Pdf for MixturePdf<'_> {
+    fn value(&self, direction: &Vec3, rng: &mut dyn Rng) -> f64 {
+        0.5 * self.p[0].value(direction, rng) + 0.5 * self.p[1].value(direction, rng)
+    }
+
+    fn generate(&self, rng: &mut dyn Rng) -> Vec3 {
+        if rng.next_f64() < 0.5 {
+            self.p[0].generate(rng)
+        } else {
+            self.p[1].generate(rng)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{CosinePdf, HittablePdf, MixturePdf, Pdf};
+    use crate::{Color, Hittable, Lambertian, Pcg32, Point3, Quad, Sphere, Vec3, PI};
+
+    #[test]
+    fn light_samples_hit_the_light() {
+        let mut rng = Pcg32::new(0, 0);
+        let mat = Arc::new(Lambertian::new_from_color(Color::new(1.0, 1.0, 1.0)));
+        let quad = Quad::new(
+            Point3::new(-1.0, -1.0, 10.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            mat.clone(),
+        );
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, 10.0), 1.0, mat);
+        for (light, solid_angle) in [
+            (&quad as &dyn Hittable, 4.0 / 100.0),
+            (&sphere, 2.0 * PI * (1.0 - (0.99f64).sqrt())),
+        ] {
+            // 1 / pdf averages out to the solid angle the light covers.
+            let pdf = HittablePdf::new(light, Point3::default());
+            let n = 2000;
+            let mut sum = 0.0;
+            for _ in 0..n {
+                let direction = pdf.generate(&mut rng);
+                let value = pdf.value(&direction, &mut rng);
+                assert!(value > 0.0);
+                sum += 1.0 / value;
+            }
+            let estimate = sum / n as f64;
+            assert!((estimate - solid_angle).abs() < 0.02 * solid_angle);
+        }
+    }
+
+    #[test]
+    fn mixture_covers_both_lobes() {
+        let mut rng = Pcg32::new(1, 0);
+        let up = CosinePdf::new(&Vec3::new(0.0, 0.0, 1.0));
+        let down = CosinePdf::new(&Vec3::new(0.0, 0.0, -1.0));
+        let mixture = MixturePdf::new(&up, &down);
+        let direction = Vec3::new(0.0, 0.0, 1.0);
+        assert!((mixture.value(&direction, &mut rng) - 0.5 / PI).abs() < 1e-12);
+        let ups = (0..1000)
+            .filter(|_| mixture.generate(&mut rng).z() > 0.0)
+            .count();
+        assert!((400..600).contains(&ups));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{rand_i32, Point3, Vec3};
+use crate::{Point3, Rng, Vec3};
 
 pub struct Perlin {
     ranvec: Vec<Vec3>,
@@ -7,20 +7,14 @@ pub struct Perlin {
     perm_z: Vec<i32>,
 }
 
-impl Default for Perlin {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 impl Perlin {
-    pub fn new() -> Self {
+    pub fn new(rng: &mut dyn Rng) -> Self {
         let ranvec = (0..Self::POINT_COUNT)
-            .map(|_| Vec3::random_range(-1.0, 1.0).unit_vector())
+            .map(|_| Vec3::random_range(rng, -1.0, 1.0).unit_vector())
             .collect();
-        let perm_x = Self::perlin_generate_perm();
-        let perm_y = Self::perlin_generate_perm();
-        let perm_z = Self::perlin_generate_perm();
+        let perm_x = Self::perlin_generate_perm(rng);
+        let perm_y = Self::perlin_generate_perm(rng);
+        let perm_z = Self::perlin_generate_perm(rng);
         Self {
             ranvec,
             perm_x,
@@ -67,18 +61,15 @@ impl Perlin {
 
     const POINT_COUNT: usize = 256;
 
-    fn perlin_generate_perm() -> Vec<i32> {
-        let mut p = vec![0; Self::POINT_COUNT];
-        for i in 0..Self::POINT_COUNT {
-            p[i] = i as i32;
-        }
-        Self::permute(&mut p, Self::POINT_COUNT);
+    fn perlin_generate_perm(rng: &mut dyn Rng) -> Vec<i32> {
+        let mut p: Vec<i32> = (0..Self::POINT_COUNT as i32).collect();
+        Self::permute(rng, &mut p, Self::POINT_COUNT);
         p
     }
 
-    fn permute(p: &mut [i32], n: usize) {
+    fn permute(rng: &mut dyn Rng, p: &mut [i32], n: usize) {
         for i in (1..n).rev() {
-            let target = rand_i32(0, i as i32) as usize;
+            let target = rng.range_i32(0, i as i32) as usize;
             p.swap(i, target);
         }
     }
@@ -88,14 +79,14 @@ impl Perlin {
         let vv = v * v * (3.0 - 2.0 * v);
         let ww = w * w * (3.0 - 2.0 * w);
         let mut acc = 0.0;
-        for i in 0..2 {
-            for j in 0..2 {
-                for k in 0..2 {
+        for (i, ci) in c.iter().enumerate() {
+            for (j, cij) in ci.iter().enumerate() {
+                for (k, cijk) in cij.iter().enumerate() {
                     let weight_v = Vec3::new(u - i as f64, v - j as f64, w - k as f64);
                     acc += (i as f64 * uu + (1.0 - i as f64) * (1.0 - uu))
                         * (j as f64 * vv + (1.0 - j as f64) * (1.0 - vv))
                         * (k as f64 * ww + (1.0 - k as f64) * (1.0 - ww))
-                        * c[i][j][k].dot(&weight_v);
+                        * cijk.dot(&weight_v);
                 }
             }
         }
@@ -0,0 +1,396 @@
+use crate::{
+    Color, GgxDielectric, HitRecord, Lobe, Material, Onb, Pdf, Ray, Rng, ScatterRecord, SolidColor,
+    Texture, TrowbridgeReitz, Vec3, PI,
+};
+
+/// Roughness of the clear coat's GGX distribution.
+const CLEARCOAT_ALPHA: f64 = 0.05;
+/// Head-on reflectance of the clear coat, a varnish of index 1.5.
+const CLEARCOAT_F0: f64 = 0.04;
+
+/// One material for plastics, metals, cloth and glass alike, after Disney's
+/// principled BSDF. Every parameter is a texture; scalar ones are read from
+/// its first channel and clamped to `[0, 1]`.
+///
+/// The layers, from the top: a clear coat; then a mix of metal, glass and a
+/// dielectric base of `base_color` with a specular highlight and sheen.
+/// Roughness is kept just above a mirror so that all of them can be sampled
+/// together.
+pub struct Principled {
+    base_color: Box<dyn Texture>,
+    metallic: Box<dyn Texture>,
+    roughness: Box<dyn Texture>,
+    specular: Box<dyn Texture>,
+    clearcoat: Box<dyn Texture>,
+    sheen: Box<dyn Texture>,
+    transmission: Box<dyn Texture>,
+    emission: Box<dyn Texture>,
+    ir: f64,
+}
+
+fn constant(value: f64) -> Box<dyn Texture> {
+    Box::new(SolidColor::new(Color::new(value, value, value)))
+}
+
+impl Principled {
+    /// A rough, non-metallic surface of `base_color`, with the usual 4%
+    /// specular reflectance and nothing else.
+    pub fn new(base_color: Box<dyn Texture>) -> Self {
+        Self {
+            base_color,
+            metallic: constant(0.0),
+            roughness: constant(0.5),
+            specular: constant(0.5),
+            clearcoat: constant(0.0),
+            sheen: constant(0.0),
+            transmission: constant(0.0),
+            emission: constant(0.0),
+            ir: 1.5,
+        }
+    }
+
+    pub fn new_from_color(color: Color) -> Self {
+        Self::new(Box::new(SolidColor::new(color)))
+    }
+
+    /// From dielectric (0) to metal (1), where `base_color` becomes the
+    /// reflectance.
+    pub fn with_metallic(mut self, metallic: Box<dyn Texture>) -> Self {
+        self.metallic = metallic;
+        self
+    }
+
+    pub fn with_roughness(mut self, roughness: Box<dyn Texture>) -> Self {
+        self.roughness = roughness;
+        self
+    }
+
+    /// Head-on reflectance of the dielectric base, where 1 stands for 8%.
+    pub fn with_specular(mut self, specular: Box<dyn Texture>) -> Self {
+        self.specular = specular;
+        self
+    }
+
+    /// Strength of a glossy varnish over everything else.
+    pub fn with_clearcoat(mut self, clearcoat: Box<dyn Texture>) -> Self {
+        self.clearcoat = clearcoat;
+        self
+    }
+
+    /// Color of the sheen that cloth shows at grazing angles.
+    pub fn with_sheen(mut self, sheen: Box<dyn Texture>) -> Self {
+        self.sheen = sheen;
+        self
+    }
+
+    /// From opaque (0) to glass (1) tinted by `base_color`.
+    pub fn with_transmission(mut self, transmission: Box<dyn Texture>) -> Self {
+        self.transmission = transmission;
+        self
+    }
+
+    pub fn with_emission(mut self, emission: Box<dyn Texture>) -> Self {
+        self.emission = emission;
+        self
+    }
+
+    /// Index of refraction of the glass.
+    pub fn with_ir(mut self, ir: f64) -> Self {
+        self.ir = ir;
+        self
+    }
+
+    fn bsdf(&self, record: &HitRecord) -> PrincipledBsdf {
+        let (u, v, p) = (record.u, record.v, &record.point);
+        let scalar = |texture: &dyn Texture| texture.value(u, v, p).x().clamp(0.0, 1.0);
+        let base_color = self.base_color.value(u, v, p);
+        let metallic = scalar(&*self.metallic);
+        let dielectric_f0 = 0.08 * scalar(&*self.specular);
+        let roughness = scalar(&*self.roughness).powi(2);
+        let distribution = TrowbridgeReitz::new(roughness.max(TrowbridgeReitz::SMOOTH_ALPHA));
+        PrincipledBsdf {
+            base_color,
+            metallic,
+            dielectric_f0,
+            f0: dielectric_f0 * (1.0 - metallic) + base_color * metallic,
+            distribution,
+            clearcoat: scalar(&*self.clearcoat),
+            sheen: self.sheen.value(u, v, p),
+            transmission: scalar(&*self.transmission) * (1.0 - metallic),
+            glass: GgxDielectric {
+                distribution,
+                eta: if record.front_face {
+                    self.ir
+                } else {
+                    1.0 / self.ir
+                },
+            },
+        }
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, ray: &Ray, record: &HitRecord, _rng: &mut dyn Rng) -> Option<ScatterRecord> {
+        let pdf = PrincipledPdf::new(&record.normal, &-ray.dir(), self.bsdf(record))?;
+        Some(ScatterRecord {
+            attenuation: self.base_color.value(record.u, record.v, &record.point),
+            lobe: Lobe::Diffuse(Box::new(pdf)),
+        })
+    }
+
+    fn emiited(&self, u: f64, v: f64, point: &Vec3) -> Color {
+        self.emission.value(u, v, point)
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, scattered: &Ray, _attenuation: Color) -> Color {
+        let uvw = Onb::new(&record.normal);
+        let wo = uvw.to_local(&-ray.dir().unit_vector());
+        let wi = uvw.to_local(&scattered.dir().unit_vector());
+        self.bsdf(record).f(&wo, &wi)
+    }
+}
+
+fn average(color: Color) -> f64 {
+    (color.x() + color.y() + color.z()) / 3.0
+}
+
+fn schlick(f0: f64, cos_theta: f64) -> f64 {
+    f0 + (1.0 - f0) * (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5)
+}
+
+fn schlick_color(f0: Color, cos_theta: f64) -> Color {
+    f0 + (1.0 - f0) * (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5)
+}
+
+/// The parameters at one point, in a shading frame with `wo` above the
+/// surface.
+#[derive(Clone, Copy)]
+struct PrincipledBsdf {
+    base_color: Color,
+    metallic: f64,
+    dielectric_f0: f64,
+    f0: Color,
+    distribution: TrowbridgeReitz,
+    clearcoat: f64,
+    sheen: Color,
+    transmission: f64,
+    glass: GgxDielectric,
+}
+
+impl PrincipledBsdf {
+    /// What the clear coat leaves for the layers below.
+    fn under_coat(&self, wo: &Vec3) -> f64 {
+        1.0 - self.clearcoat * schlick(CLEARCOAT_F0, wo.z())
+    }
+
+    /// How often each of the diffuse, specular, glass and coat lobes is
+    /// sampled, roughly in proportion to what they reflect. `None` if
+    /// nothing is reflected or transmitted at all.
+    fn lobe_weights(&self, wo: &Vec3) -> Option<[f64; 4]> {
+        let opaque = 1.0 - self.transmission;
+        let under = self.under_coat(wo);
+        let weights = [
+            under
+                * opaque
+                * (1.0 - self.metallic)
+                * (average(self.base_color) + average(self.sheen)),
+            under * opaque * average(schlick_color(self.f0, wo.z())),
+            under * self.transmission,
+            self.clearcoat * schlick(CLEARCOAT_F0, wo.z()),
+        ];
+        let total: f64 = weights.iter().sum();
+        (total > 0.0).then(|| weights.map(|w| w / total))
+    }
+
+    /// The BSDF times `|cos|` of `wi`.
+    fn f(&self, wo: &Vec3, wi: &Vec3) -> Color {
+        if wo.z() <= 0.0 || wi.z().abs() < 1e-9 {
+            return Color::zero();
+        }
+        let opaque = 1.0 - self.transmission;
+        let mut f = Color::zero();
+        let mut coat = 0.0;
+        if wi.z() > 0.0 {
+            let wm = (*wo + *wi).unit_vector();
+            // What the specular highlight doesn't reflect reaches the
+            // diffuse base.
+            let diffuse = (1.0 - self.metallic) * opaque * wi.z() / PI;
+            f += self.base_color * (diffuse * (1.0 - schlick(self.dielectric_f0, wo.z())));
+            f += self.sheen * (diffuse * (1.0 - wi.dot(&wm)).max(0.0).powi(5));
+
+            let specular = self.distribution.d(&wm) * self.distribution.g(wo, wi) / (4.0 * wo.z());
+            f += schlick_color(self.f0, wo.dot(&wm)) * (opaque * specular);
+
+            let coat_distribution = TrowbridgeReitz::new(CLEARCOAT_ALPHA);
+            coat = self.clearcoat
+                * schlick(CLEARCOAT_F0, wo.dot(&wm))
+                * coat_distribution.d(&wm)
+                * coat_distribution.g(wo, wi)
+                / (4.0 * wo.z());
+        }
+        if self.transmission > 0.0 {
+            let glass = self.transmission * self.glass.f(wo, wi);
+            f += if wi.z() < 0.0 {
+                self.base_color * glass
+            } else {
+                Color::new(glass, glass, glass)
+            };
+        }
+        f * self.under_coat(wo) + coat
+    }
+
+    fn pdf(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        if wo.z() <= 0.0 || wi.z().abs() < 1e-9 {
+            return 0.0;
+        }
+        let Some([diffuse, specular, glass, coat]) = self.lobe_weights(wo) else {
+            return 0.0;
+        };
+        diffuse * wi.z().max(0.0) / PI
+            + specular * self.distribution.reflection_pdf(wo, wi)
+            + glass * self.glass.pdf(wo, wi)
+            + coat * TrowbridgeReitz::new(CLEARCOAT_ALPHA).reflection_pdf(wo, wi)
+    }
+
+    fn sample(&self, wo: &Vec3, rng: &mut dyn Rng) -> Option<Vec3> {
+        let [diffuse, specular, glass, _] = self.lobe_weights(wo)?;
+        let pick = rng.next_f64();
+        let u = (rng.next_f64(), rng.next_f64());
+        let wi = if pick < diffuse {
+            Vec3::random_cosine_direction(rng)
+        } else if pick < diffuse + specular {
+            self.distribution.sample_reflection(wo, u)
+        } else if pick < diffuse + specular + glass {
+            self.glass.sample(wo, u, rng.next_f64())?
+        } else {
+            TrowbridgeReitz::new(CLEARCOAT_ALPHA).sample_reflection(wo, u)
+        };
+        (wi.z().abs() >= 1e-9).then_some(wi)
+    }
+}
+
+/// Samples one of a `Principled` material's lobes, with the density of all
+/// of them together.
+struct PrincipledPdf {
+    uvw: Onb,
+    wo: Vec3,
+    bsdf: PrincipledBsdf,
+}
+
+impl PrincipledPdf {
+    /// `None` if nothing is reflected or transmitted at all.
+    fn new(normal: &Vec3, wo: &Vec3, bsdf: PrincipledBsdf) -> Option<Self> {
+        let uvw = Onb::new(normal);
+        let wo = uvw.to_local(&wo.unit_vector());
+        bsdf.lobe_weights(&wo)?;
+        Some(Self { uvw, wo, bsdf })
+    }
+}
+
+impl Pdf for PrincipledPdf {
+    fn value(&self, direction: &Vec3, _rng: &mut dyn Rng) -> f64 {
+        let wi = self.uvw.to_local(&direction.unit_vector());
+        self.bsdf.pdf(&self.wo, &wi)
+    }
+
+    fn generate(&self, rng: &mut dyn Rng) -> Vec3 {
+        self.bsdf
+            .sample(&self.wo, rng)
+            .map_or(Vec3::zero(), |wi| self.uvw.transform(&wi))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{constant, Principled};
+    use crate::{
+        Color, HitRecord, Hittable, Interval, Lobe, Material, Pcg32, Point3, Ray, RotateY, Sphere,
+        Translate, Vec3, INF, PI,
+    };
+
+    #[test]
+    fn glass_knows_when_it_is_left() {
+        // Rays leaving moved glass see the inverse index.
+        let glass = Arc::new(
+            Principled::new_from_color(Color::new(1.0, 1.0, 1.0))
+                .with_transmission(constant(1.0))
+                .with_ir(1.5),
+        );
+        let ball: Arc<dyn Hittable> = Arc::new(Sphere::new(Point3::default(), 1.0, glass.clone()));
+        let moved: [Arc<dyn Hittable>; 2] = [
+            Arc::new(RotateY::new(ball.clone(), 45.0)),
+            Arc::new(Translate::new(ball, Vec3::new(0.0, 0.0, -1.5))),
+        ];
+        let mut rng = Pcg32::new(0, 0);
+        for obj in moved {
+            let ray = Ray::new(Point3::new(0.2, 0.1, 3.0), Vec3::new(0.0, 0.0, -1.0));
+            let interval = Interval::new(0.001, INF);
+            let entry = obj.hit(&ray, &interval, &mut rng).unwrap();
+            assert_eq!(glass.bsdf(&entry).glass.eta, 1.5);
+            let inside = Ray::new(entry.point, ray.dir());
+            let exit = obj.hit(&inside, &interval, &mut rng).unwrap();
+            assert_eq!(glass.bsdf(&exit).glass.eta, 1.0 / 1.5);
+        }
+    }
+
+    #[test]
+    fn lobes_are_sampled_with_their_density() {
+        // Whatever the mix of lobes, importance sampling them and sampling
+        // the sphere uniformly must estimate the same albedo, which stays
+        // below one for a white surface.
+        let materials = [
+            Principled::new_from_color(Color::new(1.0, 1.0, 1.0)),
+            Principled::new_from_color(Color::new(1.0, 1.0, 1.0)).with_metallic(constant(1.0)),
+            Principled::new_from_color(Color::new(1.0, 1.0, 1.0))
+                .with_transmission(constant(1.0))
+                .with_roughness(constant(0.7)),
+            Principled::new_from_color(Color::new(0.5, 0.5, 0.5))
+                .with_metallic(constant(0.3))
+                .with_sheen(constant(0.5))
+                .with_clearcoat(constant(1.0))
+                .with_transmission(constant(0.5))
+                .with_roughness(constant(0.8)),
+        ];
+        let mut rng = Pcg32::new(8, 0);
+        for (i, mat) in materials.into_iter().enumerate() {
+            let mat: Arc<dyn Material> = Arc::new(mat);
+            let ray = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.3, -1.0, 0.1));
+            let normal = Vec3::new(0.0, 1.0, 0.0);
+            let record =
+                HitRecord::new(&ray, Point3::default(), normal, 1.0, mat.clone(), 0.0, 0.0);
+            let srec = mat.scatter(&ray, &record, &mut rng).unwrap();
+            let Lobe::Diffuse(pdf) = srec.lobe else {
+                panic!("principled lobes are all sampled");
+            };
+            let n = 200_000;
+            let mut sampled = 0.0;
+            let mut uniform = 0.0;
+            for _ in 0..n {
+                let scattered = Ray::new(record.point, pdf.generate(&mut rng));
+                let value = if scattered.dir().near_zero() {
+                    0.0
+                } else {
+                    pdf.value(&scattered.dir(), &mut rng)
+                };
+                if value > 0.0 {
+                    let f = mat.eval(&ray, &record, &scattered, srec.attenuation);
+                    sampled += f.x() / value;
+                }
+                let sphere = Ray::new(record.point, Vec3::random_unit_vector(&mut rng));
+                let f = mat.eval(&ray, &record, &sphere, srec.attenuation);
+                uniform += f.x() * 4.0 * PI;
+            }
+            let (sampled, uniform) = (sampled / n as f64, uniform / n as f64);
+            assert!(sampled <= 1.0 && sampled > 0.2, "{}: {}", i, sampled);
+            assert!(
+                (sampled - uniform).abs() < 0.02,
+                "{}: {} vs {}",
+                i,
+                sampled,
+                uniform
+            );
+        }
+    }
+}
@@ -1,19 +1,20 @@
-use crate::{Aabb, HitRecord, Hittable, Material, Point3, Vec3};
-use std::rc::Rc;
+use crate::{Aabb, HitRecord, Hittable, Interval, Material, Point3, Ray, Rng, Vec3, INF};
+use std::sync::Arc;
 
 pub struct Quad {
     q: Point3,
     u: Vec3,
     v: Vec3,
-    mat: Rc<dyn Material>,
+    mat: Arc<dyn Material>,
     bbox: Aabb,
     normal: Vec3,
     d: f64,
     w: Vec3,
+    area: f64,
 }
 
 impl Quad {
-    pub fn new(q: Point3, u: Vec3, v: Vec3, mat: Rc<dyn Material>) -> Self {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, mat: Arc<dyn Material>) -> Self {
         let n = Vec3::cross(&u, &v);
         let normal = n.unit_vector();
         let d = normal.dot(&q);
@@ -28,6 +29,7 @@ impl Quad {
             normal,
             d,
             w,
+            area: n.length(),
         }
     }
 }
@@ -35,8 +37,9 @@ impl Quad {
 impl Hittable for Quad {
     fn hit(
         &self,
-        ray: &crate::ray::Ray,
-        interval: &crate::Interval,
+        ray: &Ray,
+        interval: &Interval,
+        _rng: &mut dyn Rng,
     ) -> Option<crate::hittable::HitRecord> {
         let denom = self.normal.dot(&ray.dir());
         if denom.abs() < 1e-8 {
@@ -56,17 +59,36 @@ impl Hittable for Quad {
             return None;
         };
 
-        Some(HitRecord::new(
-            ray,
-            interection,
-            self.normal,
-            t,
-            self.mat.clone(),
-            alpha,
-            beta,
-        ))
+        Some(
+            HitRecord::new(
+                ray,
+                interection,
+                self.normal,
+                t,
+                self.mat.clone(),
+                alpha,
+                beta,
+            )
+            .with_tangents(self.u, self.v),
+        )
     }
     fn bounding_box(&self) -> Aabb {
         self.bbox.clone()
     }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, rng: &mut dyn Rng) -> f64 {
+        let ray = Ray::new(*origin, *direction);
+        let Some(record) = self.hit(&ray, &Interval::new(0.001, INF), rng) else {
+            return 0.0;
+        };
+        // Convert the uniform density over the area to one over solid angle.
+        let distance_squared = record.t * record.t * direction.length_squared();
+        let cosine = (direction.dot(&record.normal) / direction.length()).abs();
+        distance_squared / (cosine * self.area)
+    }
+
+    fn random(&self, origin: &Point3, rng: &mut dyn Rng) -> Vec3 {
+        let p = self.q + rng.next_f64() * self.u + rng.next_f64() * self.v;
+        p - *origin
+    }
 }
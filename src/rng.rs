@@ -0,0 +1,99 @@
+/// Source of uniform random numbers. Everything that samples takes one of
+/// these explicitly, so a render is fully determined by the seeds it is fed.
+pub trait Rng {
+    fn next_u32(&mut self) -> u32;
+
+    /// Uniform in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        self.next_u32() as f64 / (1u64 << 32) as f64
+    }
+
+    /// Uniform in `[min, max)`.
+    fn range(&mut self, min: f64, max: f64) -> f64 {
+        min + (max - min) * self.next_f64()
+    }
+
+    /// Uniform integer in `[min, max]`.
+    fn range_i32(&mut self, min: i32, max: i32) -> i32 {
+        (self.range(min as f64, max as f64 + 1.0) as i32).min(max)
+    }
+}
+
+/// Any generator from the `rand` ecosystem can be plugged in as well.
+impl<R: rand::RngCore> Rng for R {
+    fn next_u32(&mut self) -> u32 {
+        rand::RngCore::next_u32(self)
+    }
+}
+
+/// PCG-XSH-RR 64/32. Small, fast, and every `stream` is an independent
+/// sequence, which is what the camera uses to give each pixel its own.
+#[derive(Debug, Clone)]
+pub struct Pcg32 {
+    state: u64,
+    inc: u64,
+}
+
+impl Pcg32 {
+    const MULTIPLIER: u64 = 6364136223846793005;
+
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            inc: (stream << 1) | 1,
+        };
+        rng.step();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.step();
+        rng
+    }
+
+    fn step(&mut self) -> u64 {
+        let old = self.state;
+        self.state = old.wrapping_mul(Self::MULTIPLIER).wrapping_add(self.inc);
+        old
+    }
+}
+
+impl Rng for Pcg32 {
+    fn next_u32(&mut self) -> u32 {
+        let old = self.step();
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pcg32, Rng};
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut a = Pcg32::new(42, 7);
+        let mut b = Pcg32::new(42, 7);
+        for _ in 0..100 {
+            assert_eq!(a.next_u32(), b.next_u32());
+        }
+    }
+
+    #[test]
+    fn streams_differ() {
+        let mut a = Pcg32::new(42, 0);
+        let mut b = Pcg32::new(42, 1);
+        let a: Vec<u32> = (0..8).map(|_| a.next_u32()).collect();
+        let b: Vec<u32> = (0..8).map(|_| b.next_u32()).collect();
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn ranges() {
+        let mut rng = Pcg32::new(1, 1);
+        for _ in 0..1000 {
+            let x = rng.range(-2.0, 3.0);
+            assert!((-2.0..3.0).contains(&x));
+            let i = rng.range_i32(0, 3);
+            assert!((0..=3).contains(&i));
+        }
+    }
+}
@@ -0,0 +1,614 @@
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+use crate::{Pcg32, Rng};
+
+/// A sequence of sample values for each pixel sample, one dimension at a
+/// time. The camera draws the pixel offset, lens point and time from the
+/// first five dimensions and then hands the sampler to the integrator as its
+/// `Rng`, so material and light sampling use the following ones. Dimensions
+/// a sampler has no structure for are filled with independent values.
+pub trait Sampler: Rng {
+    /// Starts sample `index` of the pixel at column `x`, row `y`. The values
+    /// that follow depend only on these and the sampler's seed, not on which
+    /// samples were drawn before.
+    fn start_pixel_sample(&mut self, x: usize, y: usize, index: usize);
+
+    /// The next dimension, in `[0, 1)`.
+    fn get_1d(&mut self) -> f64;
+
+    /// The next two dimensions, which samplers may stratify jointly.
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+/// The samplers a `CameraConfig` can pick by name.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplerKind {
+    #[default]
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+    BlueNoise,
+}
+
+impl SamplerKind {
+    /// A sampler of this kind for `sample_per_pixel` samples per pixel.
+    pub fn build(self, seed: u64, sample_per_pixel: usize) -> Box<dyn Sampler + Send> {
+        match self {
+            Self::Independent => Box::new(IndependentSampler::new(seed)),
+            Self::Stratified => Box::new(StratifiedSampler::new(seed, sample_per_pixel)),
+            Self::Halton => Box::new(HaltonSampler::new(seed)),
+            Self::Sobol => Box::new(SobolSampler::new(seed)),
+            Self::BlueNoise => Box::new(BlueNoiseSampler::new(seed)),
+        }
+    }
+}
+
+impl FromStr for SamplerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "independent" => Ok(Self::Independent),
+            "stratified" => Ok(Self::Stratified),
+            "halton" => Ok(Self::Halton),
+            "sobol" => Ok(Self::Sobol),
+            "blue_noise" | "blue-noise" => Ok(Self::BlueNoise),
+            _ => Err(format!("unknown sampler `{}`", s)),
+        }
+    }
+}
+
+/// Lets a sampler stand in wherever an `Rng` is expected, each draw taking
+/// the next dimension.
+macro_rules! impl_rng_for_sampler {
+    ($($sampler:ty),*) => {
+        $(
+            impl Rng for $sampler {
+                fn next_u32(&mut self) -> u32 {
+                    (self.get_1d() * (1u64 << 32) as f64) as u32
+                }
+
+                fn next_f64(&mut self) -> f64 {
+                    self.get_1d()
+                }
+            }
+        )*
+    };
+}
+
+impl_rng_for_sampler!(
+    IndependentSampler,
+    StratifiedSampler,
+    HaltonSampler,
+    SobolSampler,
+    BlueNoiseSampler
+);
+
+/// The largest `f64` below one.
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+/// Where every sampler is in its sequence, with an independent stream for
+/// dimensions past the structured ones.
+#[derive(Debug, Clone)]
+struct PixelSample {
+    seed: u64,
+    x: usize,
+    y: usize,
+    index: usize,
+    dimension: usize,
+    rng: Pcg32,
+}
+
+impl PixelSample {
+    fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            x: 0,
+            y: 0,
+            index: 0,
+            dimension: 0,
+            rng: Pcg32::new(seed, 0),
+        }
+    }
+
+    fn start(&mut self, x: usize, y: usize, index: usize) {
+        self.x = x;
+        self.y = y;
+        self.index = index;
+        self.dimension = 0;
+        let pixel = ((y as u64) << 32) | x as u64;
+        self.rng = Pcg32::new(mix_bits(self.seed ^ mix_bits(index as u64)), pixel);
+    }
+
+    /// Returns the current dimension and moves past `count` of them.
+    fn take(&mut self, count: usize) -> usize {
+        let dimension = self.dimension;
+        self.dimension += count;
+        dimension
+    }
+
+    /// A hash of the seed, pixel and `dimension`, the same for every sample
+    /// of the pixel.
+    fn pixel_hash(&self, dimension: usize) -> u64 {
+        let pixel = ((self.y as u64) << 32) | self.x as u64;
+        mix_bits(self.seed ^ mix_bits(pixel ^ mix_bits(dimension as u64 + 1)))
+    }
+}
+
+/// A 64-bit finalizer that spreads every input bit over the output.
+fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5d329728ea185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81dadef4bc2dd44d);
+    v ^= v >> 33;
+    v
+}
+
+/// Element `i` of a random permutation of `0..len` chosen by `seed`,
+/// without building the permutation (Kensler, "Correlated Multi-Jittered
+/// Sampling").
+fn permutation_element(mut i: u32, len: u32, seed: u32) -> u32 {
+    let p = seed;
+    let mut w = len - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < len {
+            break;
+        }
+    }
+    (i.wrapping_add(p)) % len
+}
+
+/// Plain uniform random values, the same as using a `Pcg32` directly.
+#[derive(Debug, Clone)]
+pub struct IndependentSampler {
+    state: PixelSample,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: PixelSample::new(seed),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, index: usize) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.state.rng.next_f64()
+    }
+}
+
+/// Jittered strata: each dimension is split into `sample_per_pixel` strata,
+/// and pairs of dimensions into a square grid, which the pixel's samples
+/// visit in a shuffled order.
+#[derive(Debug, Clone)]
+pub struct StratifiedSampler {
+    state: PixelSample,
+    sample_per_pixel: u32,
+    grid: u32,
+}
+
+impl StratifiedSampler {
+    pub fn new(seed: u64, sample_per_pixel: usize) -> Self {
+        let sample_per_pixel = sample_per_pixel.clamp(1, u32::MAX as usize) as u32;
+        Self {
+            state: PixelSample::new(seed),
+            sample_per_pixel,
+            grid: (sample_per_pixel as f64).sqrt().ceil() as u32,
+        }
+    }
+
+    fn stratum(&self, dimension: usize, count: u32) -> u32 {
+        let index = (self.state.index % count as usize) as u32;
+        permutation_element(index, count, self.state.pixel_hash(dimension) as u32)
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, index: usize) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.state.take(1);
+        let stratum = self.stratum(dimension, self.sample_per_pixel);
+        let jitter = self.state.rng.next_f64();
+        ((stratum as f64 + jitter) / self.sample_per_pixel as f64).min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        // With fewer samples than cells, the shuffle still picks every cell
+        // with the same probability.
+        let dimension = self.state.take(2);
+        let cell = self.stratum(dimension, self.grid * self.grid);
+        let (cx, cy) = (cell % self.grid, cell / self.grid);
+        let (jx, jy) = (self.state.rng.next_f64(), self.state.rng.next_f64());
+        let n = self.grid as f64;
+        (
+            ((cx as f64 + jx) / n).min(ONE_MINUS_EPSILON),
+            ((cy as f64 + jy) / n).min(ONE_MINUS_EPSILON),
+        )
+    }
+}
+
+/// Primes used as Halton bases, one per dimension.
+const PRIMES: [u64; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+/// The Halton sequence, Owen-scrambled per pixel so neighbouring pixels
+/// don't repeat each other's pattern.
+#[derive(Debug, Clone)]
+pub struct HaltonSampler {
+    state: PixelSample,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: PixelSample::new(seed),
+        }
+    }
+}
+
+/// The digits of `a` in `base`, mirrored around the radix point, with each
+/// digit permuted by a hash of the digits before it.
+fn owen_scrambled_radical_inverse(base: u64, mut a: u64, hash: u64) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_m = 1.0;
+    let mut reversed = 0u64;
+    // Stop once another digit would no longer change the result.
+    while 1.0 - (base - 1) as f64 * inv_base_m < 1.0 {
+        let next = a / base;
+        let digit = (a - next * base) as u32;
+        let digit_hash = mix_bits(hash ^ reversed) as u32;
+        let digit = permutation_element(digit, base as u32, digit_hash);
+        reversed = reversed * base + digit as u64;
+        inv_base_m *= inv_base;
+        a = next;
+    }
+    (reversed as f64 * inv_base_m).min(ONE_MINUS_EPSILON)
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, index: usize) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.state.take(1);
+        match PRIMES.get(dimension) {
+            Some(&base) => owen_scrambled_radical_inverse(
+                base,
+                self.state.index as u64,
+                self.state.pixel_hash(dimension),
+            ),
+            None => self.state.rng.next_f64(),
+        }
+    }
+}
+
+/// Primitive polynomials and initial direction numbers for the Sobol
+/// dimensions after the first, as `(degree, coefficients, m)` from Joe and
+/// Kuo's tables.
+const SOBOL_PARAMETERS: [(u32, u32, &[u32]); 20] = [
+    (1, 0, &[1]),
+    (2, 1, &[1, 3]),
+    (3, 1, &[1, 3, 1]),
+    (3, 2, &[1, 1, 1]),
+    (4, 1, &[1, 1, 3, 3]),
+    (4, 4, &[1, 3, 5, 13]),
+    (5, 2, &[1, 1, 5, 5, 17]),
+    (5, 4, &[1, 1, 5, 5, 5]),
+    (5, 7, &[1, 1, 7, 11, 19]),
+    (5, 11, &[1, 1, 5, 1, 1]),
+    (5, 13, &[1, 1, 1, 3, 11]),
+    (5, 14, &[1, 3, 5, 5, 31]),
+    (6, 1, &[1, 3, 3, 9, 7, 49]),
+    (6, 13, &[1, 1, 1, 15, 21, 21]),
+    (6, 16, &[1, 3, 1, 13, 27, 49]),
+    (6, 19, &[1, 1, 1, 15, 7, 5]),
+    (6, 22, &[1, 3, 1, 15, 13, 25]),
+    (6, 25, &[1, 1, 5, 5, 19, 61]),
+    (7, 1, &[1, 3, 7, 11, 23, 15, 103]),
+    (7, 4, &[1, 3, 7, 13, 13, 15, 69]),
+];
+
+/// Generator matrix columns for every Sobol dimension.
+fn sobol_directions() -> &'static [[u32; 32]] {
+    static DIRECTIONS: OnceLock<Vec<[u32; 32]>> = OnceLock::new();
+    DIRECTIONS.get_or_init(|| {
+        let mut directions = vec![std::array::from_fn(|k| 1u32 << (31 - k))];
+        for &(degree, coefficients, m) in &SOBOL_PARAMETERS {
+            let s = degree as usize;
+            let mut v = [0u32; 32];
+            for k in 0..32 {
+                v[k] = if k < s {
+                    m[k] << (31 - k)
+                } else {
+                    let mut value = v[k - s] ^ (v[k - s] >> s);
+                    for j in 1..s {
+                        if (coefficients >> (s - 1 - j)) & 1 == 1 {
+                            value ^= v[k - j];
+                        }
+                    }
+                    value
+                };
+            }
+            directions.push(v);
+        }
+        directions
+    })
+}
+
+fn sobol_sample(index: u64, directions: &[u32; 32]) -> u32 {
+    let mut value = 0;
+    let mut index = index;
+    let mut k = 0;
+    while index != 0 && k < 32 {
+        if index & 1 == 1 {
+            value ^= directions[k];
+        }
+        index >>= 1;
+        k += 1;
+    }
+    value
+}
+
+/// Nested uniform scrambling of the bits of `v` in the hash-based form of
+/// Laine and Karras: every bit is flipped depending on the bits above it.
+fn fast_owen_scramble(mut v: u32, seed: u32) -> u32 {
+    v = v.reverse_bits();
+    v ^= v.wrapping_mul(0x3d20adea);
+    v = v.wrapping_add(seed);
+    v = v.wrapping_mul((seed >> 16) | 1);
+    v ^= v.wrapping_mul(0x05526c56);
+    v ^= v.wrapping_mul(0x53a22864);
+    v.reverse_bits()
+}
+
+/// The Sobol sequence with Owen scrambling seeded per pixel. Sample counts
+/// that are powers of two stratify best.
+#[derive(Debug, Clone)]
+pub struct SobolSampler {
+    state: PixelSample,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: PixelSample::new(seed),
+        }
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, index: usize) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.state.take(1);
+        match sobol_directions().get(dimension) {
+            Some(directions) => {
+                let v = sobol_sample(self.state.index as u64, directions);
+                let v = fast_owen_scramble(v, self.state.pixel_hash(dimension) as u32);
+                (v as f64 / (1u64 << 32) as f64).min(ONE_MINUS_EPSILON)
+            }
+            None => self.state.rng.next_f64(),
+        }
+    }
+}
+
+/// Side of the tileable blue-noise mask.
+const BLUE_NOISE_SIZE: usize = 64;
+
+/// A tileable blue-noise mask of ranks in `0..BLUE_NOISE_SIZE²`, made with
+/// void-and-cluster: each rank goes to the empty texel farthest, in a
+/// Gaussian-weighted sense, from those ranked before it.
+fn blue_noise_mask() -> &'static [u32] {
+    static MASK: OnceLock<Vec<u32>> = OnceLock::new();
+    MASK.get_or_init(|| {
+        const N: usize = BLUE_NOISE_SIZE;
+        const RADIUS: isize = 6;
+        let sigma2 = 2.0 * 1.5 * 1.5;
+        let kernel: Vec<f64> = (-RADIUS..=RADIUS)
+            .flat_map(|dy| (-RADIUS..=RADIUS).map(move |dx| (dx, dy)))
+            .map(|(dx, dy)| (-((dx * dx + dy * dy) as f64) / sigma2).exp())
+            .collect();
+        let mut energy = vec![0.0; N * N];
+        let mut rank = vec![u32::MAX; N * N];
+        let mut rng = Pcg32::new(0x6a09e667, 0);
+        let mut next = rng.range_i32(0, (N * N - 1) as i32) as usize;
+        for r in 0..N * N {
+            rank[next] = r as u32;
+            let (x, y) = ((next % N) as isize, (next / N) as isize);
+            let side = 2 * RADIUS + 1;
+            for dy in -RADIUS..=RADIUS {
+                for dx in -RADIUS..=RADIUS {
+                    let tx = (x + dx).rem_euclid(N as isize) as usize;
+                    let ty = (y + dy).rem_euclid(N as isize) as usize;
+                    energy[ty * N + tx] += kernel[((dy + RADIUS) * side + dx + RADIUS) as usize];
+                }
+            }
+            next = (0..N * N)
+                .filter(|&i| rank[i] == u32::MAX)
+                .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+                .unwrap_or(0);
+        }
+        rank
+    })
+}
+
+/// Kronecker sequences per dimension, each offset per pixel by a tile of
+/// blue noise. Any one sample index then spreads its error over the image as
+/// high-frequency noise, which reads as finer grain than white noise.
+#[derive(Debug, Clone)]
+pub struct BlueNoiseSampler {
+    state: PixelSample,
+}
+
+impl BlueNoiseSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: PixelSample::new(seed),
+        }
+    }
+}
+
+impl Sampler for BlueNoiseSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, index: usize) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.state.take(1);
+        let Some(&prime) = PRIMES.get(dimension) else {
+            return self.state.rng.next_f64();
+        };
+        // Each dimension reads the mask at its own offset so that the
+        // dimensions of a sample aren't correlated.
+        let offset = mix_bits(self.state.seed ^ mix_bits(dimension as u64 + 1));
+        let tx = (self.state.x + offset as usize) % BLUE_NOISE_SIZE;
+        let ty = (self.state.y + (offset >> 32) as usize) % BLUE_NOISE_SIZE;
+        let rank = blue_noise_mask()[ty * BLUE_NOISE_SIZE + tx];
+        let shift = (rank as f64 + 0.5) / (BLUE_NOISE_SIZE * BLUE_NOISE_SIZE) as f64;
+        let alpha = (prime as f64).sqrt().fract();
+        (shift + alpha * self.state.index as f64)
+            .fract()
+            .min(ONE_MINUS_EPSILON)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{blue_noise_mask, sobol_directions, SamplerKind, BLUE_NOISE_SIZE};
+
+    const KINDS: [SamplerKind; 5] = [
+        SamplerKind::Independent,
+        SamplerKind::Stratified,
+        SamplerKind::Halton,
+        SamplerKind::Sobol,
+        SamplerKind::BlueNoise,
+    ];
+
+    #[test]
+    fn low_discrepancy_dimensions_are_stratified() {
+        // Each structured dimension of the stratified and Sobol samplers, and
+        // Halton's base-2 one, puts one of 16 samples in every sixteenth.
+        for kind in [SamplerKind::Stratified, SamplerKind::Sobol] {
+            let mut sampler = kind.build(5, 16);
+            for dimension in 0..sobol_directions().len() {
+                let mut hits = [0; 16];
+                for index in 0..16 {
+                    sampler.start_pixel_sample(3, 4, index);
+                    for _ in 0..dimension {
+                        sampler.get_1d();
+                    }
+                    hits[(sampler.get_1d() * 16.0) as usize] += 1;
+                }
+                assert_eq!(hits, [1; 16], "{:?} dimension {}", kind, dimension);
+            }
+        }
+        let mut halton = SamplerKind::Halton.build(5, 16);
+        let mut hits = [0; 16];
+        for index in 0..16 {
+            halton.start_pixel_sample(3, 4, index);
+            hits[(halton.get_1d() * 16.0) as usize] += 1;
+        }
+        assert_eq!(hits, [1; 16]);
+    }
+
+    #[test]
+    fn sobol_pixel_dimensions_form_a_net() {
+        // The first 2^m points of the two leading Sobol dimensions put one
+        // point in every 2^a by 2^b box with a + b = m, scrambled or not.
+        let mut sampler = SamplerKind::Sobol.build(9, 64);
+        let points: Vec<(f64, f64)> = (0..64)
+            .map(|index| {
+                sampler.start_pixel_sample(0, 0, index);
+                sampler.get_2d()
+            })
+            .collect();
+        for a in 0..=6 {
+            let (nx, ny) = (1 << a, 1 << (6 - a));
+            let mut hits = vec![0; 64];
+            for &(x, y) in &points {
+                hits[(y * ny as f64) as usize * nx + (x * nx as f64) as usize] += 1;
+            }
+            assert!(hits.iter().all(|&h| h == 1), "{} by {}", nx, ny);
+        }
+    }
+
+    #[test]
+    fn samplers_are_unbiased_and_repeatable() {
+        // Averages x * y over pixels and samples, which should be 1/4, and
+        // restarting a sample gives back the same values.
+        for kind in KINDS {
+            let mut sampler = kind.build(1, 8);
+            let mut sum = 0.0;
+            let mut count = 0;
+            for pixel in 0..256 {
+                for index in 0..8 {
+                    sampler.start_pixel_sample(pixel % 16, pixel / 16, index);
+                    let (x, y) = sampler.get_2d();
+                    let z = sampler.next_f64();
+                    assert!((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y));
+                    assert!((0.0..1.0).contains(&z));
+                    sampler.start_pixel_sample(pixel % 16, pixel / 16, index);
+                    assert_eq!(sampler.get_2d(), (x, y), "{:?}", kind);
+                    sum += x * y;
+                    count += 1;
+                }
+            }
+            let mean = sum / count as f64;
+            assert!((mean - 0.25).abs() < 0.01, "{:?}: {}", kind, mean);
+        }
+    }
+
+    #[test]
+    fn blue_noise_mask_ranks_every_texel() {
+        let mut ranks = blue_noise_mask().to_vec();
+        ranks.sort_unstable();
+        let expected: Vec<u32> = (0..(BLUE_NOISE_SIZE * BLUE_NOISE_SIZE) as u32).collect();
+        assert_eq!(ranks, expected);
+    }
+}
@@ -0,0 +1,898 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::{
+    create_box, load_obj, BVHNode, BumpMap, CameraConfig, CheckerTexture, Color, Conductor,
+    ConstantMedium, Dielectric, DiffuseLight, Error, Hittable, HittableList, ImageTexture,
+    Isotropic, Lambertian, Material, Metal, NoiseTexture, NormalMap, Point3, Principled, Quad,
+    Result, Rng, RotateY, RoughDielectric, SolidColor, Sphere, Texture, Translate, Triangle, Vec3,
+};
+
+/// A world to render, the subset of it to sample as lights, and the camera
+/// it wants.
+pub struct Scene {
+    pub world: HittableList,
+    pub lights: HittableList,
+    pub camera: CameraConfig,
+    /// Problems that did not stop the scene from loading, such as an image
+    /// replaced by a placeholder or an unknown OBJ material, for the caller
+    /// to report.
+    pub warnings: Vec<String>,
+}
+
+impl Scene {
+    /// A scene with no lights to sample.
+    pub fn new(world: HittableList, camera: CameraConfig) -> Self {
+        Self {
+            world,
+            lights: HittableList::new(),
+            camera,
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn with_lights(mut self, lights: HittableList) -> Self {
+        self.lights = lights;
+        self
+    }
+}
+
+/// Reads a TOML scene file. Relative image paths are resolved against the
+/// directory the file lives in.
+pub fn load_scene(path: impl AsRef<Path>, rng: &mut dyn Rng) -> Result<Scene> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path).map_err(|source| Error::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse_scene(&text, path.parent().unwrap_or(Path::new("")), rng).map_err(|err| match err {
+        Error::SceneSyntax { path: None, source } => Error::SceneSyntax {
+            path: Some(path.to_path_buf()),
+            source,
+        },
+        err => err,
+    })
+}
+
+pub fn parse_scene(text: &str, base_dir: &Path, rng: &mut dyn Rng) -> Result<Scene> {
+    let def: SceneDef =
+        toml::from_str(text).map_err(|source| Error::SceneSyntax { path: None, source })?;
+    let mut builder = Builder {
+        textures: &def.textures,
+        base_dir,
+        rng,
+        built_textures: HashMap::new(),
+        materials: HashMap::new(),
+        lights: HittableList::new(),
+        warnings: Vec::new(),
+    };
+    // Sorted by name, so noise textures draw from `rng` in the same order on
+    // every run.
+    for (name, mat) in &def.materials {
+        let mat = builder.material(mat)?;
+        builder.materials.insert(name.clone(), mat);
+    }
+
+    let mut world = HittableList::new();
+    for obj in &def.objects {
+        world.add(builder.object(obj)?);
+    }
+    if def.bvh && !world.list().is_empty() {
+        world = HittableList::new_from_node(Arc::new(BVHNode::new(world.into())));
+    }
+    let lights = std::mem::take(&mut builder.lights);
+    let mut scene = Scene::new(world, def.camera).with_lights(lights);
+    scene.warnings = builder.warnings;
+    Ok(scene)
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDef {
+    #[serde(default)]
+    camera: CameraConfig,
+    #[serde(default)]
+    textures: BTreeMap<String, TextureDef>,
+    #[serde(default)]
+    materials: BTreeMap<String, MaterialDef>,
+    #[serde(default)]
+    objects: Vec<ObjectDef>,
+    /// Wraps the whole world in a BVH.
+    #[serde(default)]
+    bvh: bool,
+}
+
+/// Either an inline `[r, g, b]`, a gray level, or the name of an entry in
+/// `[textures]`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TextureRef {
+    Color(Color),
+    Value(f64),
+    Named(String),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDef {
+    Solid {
+        color: Color,
+    },
+    Checker {
+        odd: TextureRef,
+        even: TextureRef,
+        scale: f64,
+    },
+    Image {
+        path: PathBuf,
+        /// Render a placeholder instead of failing when the file is unusable.
+        #[serde(default)]
+        fallback: bool,
+    },
+    Noise {
+        scale: f64,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDef {
+    Lambertian {
+        albedo: TextureRef,
+    },
+    Metal {
+        albedo: Color,
+        #[serde(default)]
+        fuzz: f64,
+    },
+    /// A GGX metal, either one of the `metal` presets or given by its
+    /// complex index of refraction `eta` and `k`.
+    Conductor {
+        metal: Option<MetalPreset>,
+        eta: Option<Color>,
+        k: Option<Color>,
+        roughness: TextureRef,
+    },
+    /// Glass, optionally tinted by an `absorption` coefficient or by the
+    /// `transmittance` left after `distance` (default 1) inside it.
+    Dielectric {
+        ir: f64,
+        absorption: Option<Color>,
+        transmittance: Option<Color>,
+        distance: Option<f64>,
+    },
+    /// Frosted glass, with GGX microfacets.
+    RoughDielectric {
+        ir: f64,
+        roughness: TextureRef,
+    },
+    DiffuseLight {
+        emit: TextureRef,
+    },
+    /// The uber material; parameters left out keep `Principled`'s defaults.
+    Principled {
+        base_color: TextureRef,
+        metallic: Option<TextureRef>,
+        roughness: Option<TextureRef>,
+        specular: Option<TextureRef>,
+        clearcoat: Option<TextureRef>,
+        sheen: Option<TextureRef>,
+        transmission: Option<TextureRef>,
+        emission: Option<TextureRef>,
+        ir: Option<f64>,
+    },
+    Isotropic {
+        albedo: TextureRef,
+    },
+    /// Shades the inline `material` with a tangent space normal map.
+    NormalMap {
+        material: Box<MaterialDef>,
+        map: TextureRef,
+    },
+    /// Shades the inline `material` as if raised by `scale` (default 1)
+    /// times `height`.
+    BumpMap {
+        material: Box<MaterialDef>,
+        height: TextureRef,
+        scale: Option<f64>,
+        step: Option<f64>,
+    },
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum MetalPreset {
+    Gold,
+    Copper,
+    Aluminium,
+    Silver,
+}
+
+/// An object is one table holding both the shape's keys and its own, but
+/// `deny_unknown_fields` doesn't work with `#[serde(flatten)]`. So its own
+/// keys are taken out first and the rest must all belong to the shape.
+#[derive(Deserialize)]
+#[serde(try_from = "toml::Table")]
+struct ObjectDef {
+    shape: ShapeDef,
+    /// Applied in order, so `[{ rotate_y = 15 }, { translate = [1, 0, 0] }]`
+    /// rotates first.
+    transforms: Vec<TransformDef>,
+    /// Turns the object into the boundary of a constant-density volume.
+    medium: Option<MediumDef>,
+    /// Also samples the object directly as a light source.
+    light: bool,
+}
+
+impl TryFrom<toml::Table> for ObjectDef {
+    type Error = toml::de::Error;
+
+    fn try_from(mut table: toml::Table) -> std::result::Result<Self, Self::Error> {
+        let transforms = table.remove("transforms").map(toml::Value::try_into);
+        let medium = table.remove("medium").map(toml::Value::try_into);
+        let light = table.remove("light").map(toml::Value::try_into);
+        Ok(Self {
+            shape: toml::Value::Table(table).try_into()?,
+            transforms: transforms.transpose()?.unwrap_or_default(),
+            medium: medium.transpose()?,
+            light: light.transpose()?.unwrap_or_default(),
+        })
+    }
+}
+
+impl ObjectDef {
+    /// Whether the object implements `pdf_value` and `random`, so it can be
+    /// sampled as a light. Transforms pass sampling through, media and BVHs
+    /// don't.
+    fn can_be_sampled(&self) -> bool {
+        self.medium.is_none()
+            && match &self.shape {
+                ShapeDef::Sphere { .. } | ShapeDef::Quad { .. } | ShapeDef::Box { .. } => true,
+                ShapeDef::Triangle { .. } | ShapeDef::Mesh { .. } => false,
+                ShapeDef::Group { objects, bvh } => {
+                    !bvh && objects.iter().all(ObjectDef::can_be_sampled)
+                }
+            }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ShapeDef {
+    Sphere {
+        center: Point3,
+        radius: f64,
+        material: String,
+        /// Where the sphere is at time 1 if it moves.
+        center2: Option<Point3>,
+    },
+    Quad {
+        q: Point3,
+        u: Vec3,
+        v: Vec3,
+        material: String,
+    },
+    Triangle {
+        v0: Point3,
+        v1: Point3,
+        v2: Point3,
+        material: String,
+        normals: Option<[Vec3; 3]>,
+        uvs: Option<[(f64, f64); 3]>,
+    },
+    Box {
+        a: Point3,
+        b: Point3,
+        material: String,
+    },
+    /// A Wavefront OBJ file. `material` replaces whatever its MTL file says.
+    Mesh {
+        path: PathBuf,
+        material: Option<String>,
+    },
+    Group {
+        objects: Vec<ObjectDef>,
+        #[serde(default)]
+        bvh: bool,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum TransformDef {
+    Translate(Vec3),
+    RotateY(f64),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MediumDef {
+    density: f64,
+    albedo: TextureRef,
+}
+
+struct Builder<'a> {
+    textures: &'a BTreeMap<String, TextureDef>,
+    base_dir: &'a Path,
+    rng: &'a mut dyn Rng,
+    /// Named textures already built, shared by everything that names them.
+    built_textures: HashMap<String, Arc<dyn Texture>>,
+    materials: HashMap<String, Arc<dyn Material>>,
+    lights: HittableList,
+    warnings: Vec<String>,
+}
+
+impl Builder<'_> {
+    fn texture(&mut self, texture: &TextureRef) -> Result<Box<dyn Texture>> {
+        self.texture_inner(texture, &mut Vec::new())
+    }
+
+    fn texture_inner(
+        &mut self,
+        texture: &TextureRef,
+        visiting: &mut Vec<String>,
+    ) -> Result<Box<dyn Texture>> {
+        let name = match texture {
+            TextureRef::Color(color) => return Ok(Box::new(SolidColor::new(*color))),
+            TextureRef::Value(v) => return Ok(Box::new(SolidColor::new_from_rgb(*v, *v, *v))),
+            TextureRef::Named(name) => name,
+        };
+        if let Some(texture) = self.built_textures.get(name) {
+            return Ok(Box::new(texture.clone()));
+        }
+        if visiting.contains(name) {
+            return Err(Error::TextureCycle(name.clone()));
+        }
+        let textures = self.textures;
+        let def = textures
+            .get(name)
+            .ok_or_else(|| Error::UnknownTexture(name.clone()))?;
+        visiting.push(name.clone());
+        let texture: Arc<dyn Texture> = match def {
+            TextureDef::Solid { color } => Arc::new(SolidColor::new(*color)),
+            TextureDef::Checker { odd, even, scale } => Arc::new(CheckerTexture::new(
+                self.texture_inner(odd, visiting)?,
+                self.texture_inner(even, visiting)?,
+                *scale,
+            )),
+            TextureDef::Image { path, fallback } => {
+                let path = self.base_dir.join(path);
+                if *fallback {
+                    let (texture, err) = ImageTexture::new_or_placeholder(path);
+                    if let Some(err) = err {
+                        self.warnings
+                            .push(format!("{}, using a placeholder texture", err));
+                    }
+                    Arc::new(texture)
+                } else {
+                    Arc::new(ImageTexture::new(path)?)
+                }
+            }
+            TextureDef::Noise { scale } => Arc::new(NoiseTexture::new(*scale, self.rng)),
+        };
+        visiting.pop();
+        self.built_textures.insert(name.clone(), texture.clone());
+        Ok(Box::new(texture))
+    }
+
+    fn material(&mut self, def: &MaterialDef) -> Result<Arc<dyn Material>> {
+        Ok(match def {
+            MaterialDef::Lambertian { albedo } => Arc::new(Lambertian::new(self.texture(albedo)?)),
+            MaterialDef::Metal { albedo, fuzz } => Arc::new(Metal::new(*albedo, *fuzz)),
+            MaterialDef::Conductor {
+                metal,
+                eta,
+                k,
+                roughness,
+            } => {
+                let roughness = self.texture(roughness)?;
+                Arc::new(match (metal, eta, k) {
+                    (Some(MetalPreset::Gold), None, None) => Conductor::gold(roughness),
+                    (Some(MetalPreset::Copper), None, None) => Conductor::copper(roughness),
+                    (Some(MetalPreset::Aluminium), None, None) => Conductor::aluminium(roughness),
+                    (Some(MetalPreset::Silver), None, None) => Conductor::silver(roughness),
+                    (None, Some(eta), Some(k)) => Conductor::new(*eta, *k, roughness),
+                    _ => {
+                        return Err(Error::InvalidMaterial(
+                            "a conductor needs either `metal` or both `eta` and `k`",
+                        ))
+                    }
+                })
+            }
+            MaterialDef::Dielectric {
+                ir,
+                absorption,
+                transmittance,
+                distance,
+            } => {
+                let glass = Dielectric::new(*ir);
+                Arc::new(match (absorption, transmittance, distance) {
+                    (None, None, None) => glass,
+                    (Some(absorption), None, None) => glass.with_absorption(*absorption),
+                    (None, Some(color), distance) => {
+                        let distance = distance.unwrap_or(1.0);
+                        if distance <= 0.0 {
+                            return Err(Error::InvalidMaterial(
+                                "a dielectric `distance` must be positive",
+                            ));
+                        }
+                        if [color.x(), color.y(), color.z()]
+                            .iter()
+                            .any(|&c| !(c > 0.0 && c <= 1.0))
+                        {
+                            return Err(Error::InvalidMaterial(
+                                "a dielectric `transmittance` must be in (0, 1]",
+                            ));
+                        }
+                        glass.with_transmittance(*color, distance)
+                    }
+                    _ => return Err(Error::InvalidMaterial(
+                        "a dielectric takes either `absorption` or `transmittance` and `distance`",
+                    )),
+                })
+            }
+            MaterialDef::RoughDielectric { ir, roughness } => {
+                Arc::new(RoughDielectric::new(*ir, self.texture(roughness)?))
+            }
+            MaterialDef::DiffuseLight { emit } => Arc::new(DiffuseLight::new(self.texture(emit)?)),
+            MaterialDef::Principled {
+                base_color,
+                metallic,
+                roughness,
+                specular,
+                clearcoat,
+                sheen,
+                transmission,
+                emission,
+                ir,
+            } => {
+                let mut mat = Principled::new(self.texture(base_color)?);
+                if let Some(metallic) = metallic {
+                    mat = mat.with_metallic(self.texture(metallic)?);
+                }
+                if let Some(roughness) = roughness {
+                    mat = mat.with_roughness(self.texture(roughness)?);
+                }
+                if let Some(specular) = specular {
+                    mat = mat.with_specular(self.texture(specular)?);
+                }
+                if let Some(clearcoat) = clearcoat {
+                    mat = mat.with_clearcoat(self.texture(clearcoat)?);
+                }
+                if let Some(sheen) = sheen {
+                    mat = mat.with_sheen(self.texture(sheen)?);
+                }
+                if let Some(transmission) = transmission {
+                    mat = mat.with_transmission(self.texture(transmission)?);
+                }
+                if let Some(emission) = emission {
+                    mat = mat.with_emission(self.texture(emission)?);
+                }
+                if let Some(ir) = ir {
+                    mat = mat.with_ir(*ir);
+                }
+                Arc::new(mat)
+            }
+            MaterialDef::Isotropic { albedo } => Arc::new(Isotropic::new(self.texture(albedo)?)),
+            MaterialDef::NormalMap { material, map } => {
+                Arc::new(NormalMap::new(self.material(material)?, self.texture(map)?))
+            }
+            MaterialDef::BumpMap {
+                material,
+                height,
+                scale,
+                step,
+            } => {
+                let material = self.material(material)?;
+                let bump = BumpMap::new(material, self.texture(height)?, scale.unwrap_or(1.0));
+                Arc::new(match step {
+                    Some(step) => bump.with_step(*step),
+                    None => bump,
+                })
+            }
+        })
+    }
+
+    fn named_material(&self, name: &str) -> Result<Arc<dyn Material>> {
+        self.materials
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::UnknownMaterial(name.to_string()))
+    }
+
+    fn object(&mut self, def: &ObjectDef) -> Result<Arc<dyn Hittable>> {
+        let mut obj: Arc<dyn Hittable> = match &def.shape {
+            ShapeDef::Sphere {
+                center,
+                radius,
+                material,
+                center2,
+            } => {
+                let mat = self.named_material(material)?;
+                match center2 {
+                    Some(center2) => Arc::new(Sphere::new_moving(*center, *center2, *radius, mat)),
+                    None => Arc::new(Sphere::new(*center, *radius, mat)),
+                }
+            }
+            ShapeDef::Quad { q, u, v, material } => {
+                Arc::new(Quad::new(*q, *u, *v, self.named_material(material)?))
+            }
+            ShapeDef::Triangle {
+                v0,
+                v1,
+                v2,
+                material,
+                normals,
+                uvs,
+            } => {
+                let mut tri = Triangle::new(*v0, *v1, *v2, self.named_material(material)?);
+                if let Some(normals) = normals {
+                    tri = tri.with_normals(*normals);
+                }
+                if let Some(uvs) = uvs {
+                    tri = tri.with_uvs(*uvs);
+                }
+                Arc::new(tri)
+            }
+            ShapeDef::Box { a, b, material } => {
+                Arc::new(create_box(*a, *b, self.named_material(material)?))
+            }
+            ShapeDef::Mesh { path, material } => {
+                let (mut mesh, warnings) = load_obj(self.base_dir.join(path))?;
+                self.warnings.extend(warnings);
+                if let Some(material) = material {
+                    mesh = mesh.with_material(self.named_material(material)?);
+                }
+                Arc::new(mesh)
+            }
+            ShapeDef::Group { objects, bvh } => {
+                let mut group = HittableList::new();
+                for obj in objects {
+                    group.add(self.object(obj)?);
+                }
+                if *bvh && !group.list().is_empty() {
+                    Arc::new(BVHNode::new(group.into()))
+                } else {
+                    Arc::new(group)
+                }
+            }
+        };
+        for transform in &def.transforms {
+            obj = match transform {
+                TransformDef::Translate(offset) => Arc::new(Translate::new(obj, *offset)),
+                TransformDef::RotateY(angle) => Arc::new(RotateY::new(obj, *angle)),
+            };
+        }
+        if let Some(medium) = &def.medium {
+            let albedo = self.texture(&medium.albedo)?;
+            obj = Arc::new(ConstantMedium::new(obj, medium.density, albedo));
+        }
+        if def.light {
+            if !def.can_be_sampled() {
+                return Err(Error::InvalidLight(
+                    "only spheres, quads, boxes and groups of them without a BVH or medium \
+                     can be sampled",
+                ));
+            }
+            self.lights.add(obj.clone());
+        }
+        Ok(obj)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::parse_scene;
+    use crate::{Color, Error, Hittable, Interval, Pcg32, Point3, Ray, Vec3, INF};
+
+    #[test]
+    fn noise_is_seeded_in_a_fixed_order() {
+        // The albedo of a sphere is the noise its material names, seen at
+        // the same point whichever material the sphere has. The same seed
+        // must give the same noise, and one named texture the same noise
+        // twice.
+        let materials = r#"
+            [textures.a]
+            type = "noise"
+            scale = 3
+
+            [textures.b]
+            type = "noise"
+            scale = 3
+
+            [materials.first]
+            type = "lambertian"
+            albedo = "b"
+
+            [materials.second]
+            type = "lambertian"
+            albedo = "a"
+
+            [materials.third]
+            type = "lambertian"
+            albedo = "b"
+        "#;
+        let albedo = |material: &str| {
+            let text = format!(
+                r#"{materials}
+                [[objects]]
+                type = "sphere"
+                center = [0, 0, 0]
+                radius = 1
+                material = "{material}"
+                "#
+            );
+            let mut rng = Pcg32::new(3, 0);
+            let scene = parse_scene(&text, Path::new(""), &mut rng).unwrap();
+            let ray = Ray::new(Point3::new(0.2, 0.3, 5.0), Vec3::new(0.0, 0.0, -1.0));
+            let rec = scene
+                .world
+                .hit(&ray, &Interval::new(0.001, INF), &mut rng)
+                .unwrap();
+            rec.mat.scatter(&ray, &rec, &mut rng).unwrap().attenuation
+        };
+        let first: Color = albedo("first");
+        assert_eq!(albedo("first"), first);
+        assert_eq!(albedo("third"), first);
+        assert_ne!(albedo("second"), first);
+    }
+
+    #[test]
+    fn cornell_box_example() {
+        let text = include_str!("../scenes/cornell_box.toml");
+        let mut rng = Pcg32::new(0, 0);
+        let scene = parse_scene(text, Path::new("scenes"), &mut rng).unwrap();
+        assert_eq!(scene.world.list().len(), 1);
+        assert_eq!(scene.lights.list().len(), 1);
+        let camera = scene.camera;
+        assert_eq!(camera.lookfrom, Point3::new(278.0, 278.0, -800.0));
+        assert_eq!(camera.image_width, 600);
+    }
+
+    #[test]
+    fn unknown_material() {
+        let text = r#"
+            [[objects]]
+            type = "sphere"
+            center = [0, 0, 0]
+            radius = 1
+            material = "missing"
+        "#;
+        let mut rng = Pcg32::new(0, 0);
+        let err = parse_scene(text, Path::new(""), &mut rng).err().unwrap();
+        assert!(matches!(err, Error::UnknownMaterial(name) if name == "missing"));
+    }
+
+    #[test]
+    fn conductor_presets() {
+        let text = r#"
+            [materials.gold]
+            type = "conductor"
+            metal = "gold"
+            roughness = 0.3
+
+            [materials.custom]
+            type = "conductor"
+            eta = [0.2, 0.9, 1.1]
+            k = [3.9, 2.4, 2.1]
+            roughness = 0
+        "#;
+        let mut rng = Pcg32::new(0, 0);
+        assert!(parse_scene(text, Path::new(""), &mut rng).is_ok());
+
+        let text = text.replace("eta = [0.2, 0.9, 1.1]", "metal = \"silver\"");
+        let err = parse_scene(&text, Path::new(""), &mut rng).err().unwrap();
+        assert!(matches!(err, Error::InvalidMaterial(_)));
+    }
+
+    #[test]
+    fn tinted_glass() {
+        let text = r#"
+            [materials.clear]
+            type = "dielectric"
+            ir = 1.5
+
+            [materials.green]
+            type = "dielectric"
+            ir = 1.5
+            transmittance = [0.6, 0.9, 0.6]
+            distance = 2
+
+            [materials.blue]
+            type = "dielectric"
+            ir = 1.5
+            absorption = [0.5, 0.2, 0]
+        "#;
+        let mut rng = Pcg32::new(0, 0);
+        assert!(parse_scene(text, Path::new(""), &mut rng).is_ok());
+
+        for (from, to) in [
+            ("absorption", "distance = 1\nabsorption"),
+            ("distance = 2", "distance = 0"),
+            ("distance = 2", "distance = -1"),
+            ("[0.6, 0.9, 0.6]", "[0.6, 0, 0.6]"),
+            ("[0.6, 0.9, 0.6]", "[0.6, 1.2, 0.6]"),
+        ] {
+            let text = text.replace(from, to);
+            let err = parse_scene(&text, Path::new(""), &mut rng).err().unwrap();
+            assert!(matches!(err, Error::InvalidMaterial(_)), "{}", to);
+        }
+    }
+
+    #[test]
+    fn principled_parameters() {
+        let text = r#"
+            [textures.rust]
+            type = "checker"
+            scale = 0.1
+            even = 0.2
+            odd = 0.9
+
+            [materials.painted]
+            type = "principled"
+            base_color = [0.8, 0.1, 0.1]
+            metallic = "rust"
+            roughness = 0.4
+            clearcoat = 1
+            sheen = [0.2, 0.2, 0.3]
+            ir = 1.4
+        "#;
+        let mut rng = Pcg32::new(0, 0);
+        assert!(parse_scene(text, Path::new(""), &mut rng).is_ok());
+
+        let text = text.replace("ir = 1.4", "anisotropy = 0.5");
+        assert!(parse_scene(&text, Path::new(""), &mut rng).is_err());
+    }
+
+    #[test]
+    fn wrapped_materials() {
+        let text = r#"
+            [textures.plaster]
+            type = "noise"
+            scale = 4
+
+            [materials.wall]
+            type = "bump_map"
+            height = "plaster"
+            scale = 0.02
+            material = { type = "lambertian", albedo = [0.8, 0.8, 0.7] }
+
+            [materials.tiles]
+            type = "normal_map"
+            map = [0.5, 0.5, 1]
+
+            [materials.tiles.material]
+            type = "bump_map"
+            height = 0
+            material = { type = "metal", albedo = [0.9, 0.9, 0.9] }
+        "#;
+        let mut rng = Pcg32::new(0, 0);
+        assert!(parse_scene(text, Path::new(""), &mut rng).is_ok());
+    }
+
+    #[test]
+    fn missing_image() {
+        let text = r#"
+            [textures.map]
+            type = "image"
+            path = "does-not-exist.png"
+
+            [materials.m]
+            type = "lambertian"
+            albedo = "map"
+        "#;
+        let mut rng = Pcg32::new(0, 0);
+        let err = parse_scene(text, Path::new(""), &mut rng).err().unwrap();
+        assert!(matches!(&err, Error::Io { path, .. } if path.ends_with("does-not-exist.png")));
+        assert!(err.to_string().starts_with("does-not-exist.png: "));
+
+        let text = text.replace("type = \"image\"", "type = \"image\"\nfallback = true");
+        let scene = parse_scene(&text, Path::new(""), &mut rng).unwrap();
+        assert_eq!(scene.warnings.len(), 1);
+        assert!(scene.warnings[0].ends_with(", using a placeholder texture"));
+    }
+
+    #[test]
+    fn texture_cycle() {
+        let text = r#"
+            [textures.a]
+            type = "checker"
+            odd = "b"
+            even = [1, 1, 1]
+            scale = 1
+
+            [textures.b]
+            type = "checker"
+            odd = "a"
+            even = [0, 0, 0]
+            scale = 1
+
+            [materials.m]
+            type = "lambertian"
+            albedo = "a"
+        "#;
+        let mut rng = Pcg32::new(0, 0);
+        let err = parse_scene(text, Path::new(""), &mut rng).err().unwrap();
+        assert!(matches!(err, Error::TextureCycle(_)));
+    }
+
+    #[test]
+    fn lights_must_be_sampleable() {
+        let text = r#"
+            [materials.lamp]
+            type = "diffuse_light"
+            emit = [4, 4, 4]
+
+            [[objects]]
+            type = "group"
+            light = true
+            transforms = [{ rotate_y = 30 }]
+            objects = [
+                { type = "quad", q = [0, 1, 0], u = [1, 0, 0], v = [0, 0, 1], material = "lamp" },
+                { type = "sphere", center = [0, 2, 0], radius = 0.5, material = "lamp" },
+            ]
+        "#;
+        let mut rng = Pcg32::new(0, 0);
+        let scene = parse_scene(text, Path::new(""), &mut rng).unwrap();
+        assert_eq!(scene.lights.list().len(), 1);
+
+        for shape in [
+            r#"type = "triangle"
+            v0 = [0, 1, 0]
+            v1 = [1, 1, 0]
+            v2 = [0, 1, 1]
+            material = "lamp""#,
+            r#"type = "sphere"
+            center = [0, 1, 0]
+            radius = 1
+            material = "lamp"
+            medium = { density = 1, albedo = 1 }"#,
+            r#"type = "group"
+            bvh = true
+            objects = [{ type = "sphere", center = [0, 1, 0], radius = 1, material = "lamp" }]"#,
+        ] {
+            let text = format!(
+                "[materials.lamp]\ntype = \"diffuse_light\"\nemit = [4, 4, 4]\n\n\
+                 [[objects]]\n{}\nlight = true\n",
+                shape
+            );
+            let err = parse_scene(&text, Path::new(""), &mut rng).err().unwrap();
+            assert!(matches!(err, Error::InvalidLight(_)), "{}", shape);
+        }
+    }
+    #[test]
+    fn misspelled_object_keys() {
+        let text = r#"
+            [materials.m]
+            type = "lambertian"
+            albedo = 0.5
+
+            [[objects]]
+            type = "group"
+            bvh = true
+            objects = [
+                { type = "sphere", center = [0, 0, 0], radius = 1, material = "m", light = false },
+            ]
+            transforms = [{ translate = [0, 1, 0] }]
+        "#;
+        let mut rng = Pcg32::new(0, 0);
+        assert!(parse_scene(text, Path::new(""), &mut rng).is_ok());
+
+        for (key, typo) in [
+            ("bvh", "bhv"),
+            ("light", "ligth"),
+            ("transforms", "transfroms"),
+        ] {
+            let text = text.replacen(key, typo, 1);
+            let err = parse_scene(&text, Path::new(""), &mut rng).err().unwrap();
+            assert!(matches!(err, Error::SceneSyntax { .. }), "{}", typo);
+            assert!(err.to_string().contains(typo), "{}", err);
+        }
+    }
+}
@@ -1,17 +1,17 @@
-use crate::{Aabb, HitRecord, Hittable, Interval, Material, Point3, Vec3};
-use std::rc::Rc;
+use crate::{Aabb, HitRecord, Hittable, Interval, Material, Onb, Point3, Ray, Rng, Vec3, INF, PI};
+use std::sync::Arc;
 
 pub struct Sphere {
     center: Point3,
     radius: f64,
-    mat: Rc<dyn Material>,
+    mat: Arc<dyn Material>,
     center_vec: Vec3,
     is_moving: bool,
     bbox: Aabb,
 }
 
 impl Sphere {
-    pub fn new(center: Point3, radius: f64, mat: Rc<dyn Material>) -> Self {
+    pub fn new(center: Point3, radius: f64, mat: Arc<dyn Material>) -> Self {
         let rvec = Vec3::new(radius, radius, radius);
         let bbox = Aabb::new_from_points(center - rvec, center + rvec);
         Self {
@@ -24,7 +24,12 @@ impl Sphere {
         }
     }
 
-    pub fn new_moving(center: Point3, center2: Point3, radius: f64, mat: Rc<dyn Material>) -> Self {
+    pub fn new_moving(
+        center: Point3,
+        center2: Point3,
+        radius: f64,
+        mat: Arc<dyn Material>,
+    ) -> Self {
         let rvec = Vec3::new(radius, radius, radius);
         let box1 = Aabb::new_from_points(center - rvec, center + rvec);
         let box2 = Aabb::new_from_points(center2 - rvec, center2 + rvec);
@@ -49,10 +54,23 @@ impl Sphere {
         let v = theta / std::f64::consts::PI;
         (u, v)
     }
+
+    /// dp/du and dp/dv at `point` on the unit sphere, matching `get_uv`.
+    /// Both vanish at the poles.
+    fn get_tangents(&self, point: Point3) -> (Vec3, Vec3) {
+        let (x, y, z) = (point.x(), point.y(), point.z());
+        let sin_theta = (1.0 - y * y).max(0.0).sqrt();
+        if sin_theta == 0.0 {
+            return (Vec3::zero(), Vec3::zero());
+        }
+        let dpdu = 2.0 * PI * self.radius * Vec3::new(z, 0.0, -x);
+        let dpdv = PI * self.radius * Vec3::new(-y * x, sin_theta * sin_theta, -y * z) / sin_theta;
+        (dpdu, dpdv)
+    }
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, ray: &crate::ray::Ray, interval: &Interval) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, interval: &Interval, _rng: &mut dyn Rng) -> Option<HitRecord> {
         let center = if self.is_moving {
             self.sphere_center(ray.time())
         } else {
@@ -76,18 +94,81 @@ impl Hittable for Sphere {
         let point = ray.at(t);
         let normal = (point - self.center) / self.radius;
         let (u, v) = self.get_uv(normal);
-        Some(HitRecord::new(
-            ray,
-            point,
-            normal,
-            t,
-            self.mat.clone(),
-            u,
-            v,
-        ))
+        let (tangent, bitangent) = self.get_tangents(normal);
+        Some(
+            HitRecord::new(ray, point, normal, t, self.mat.clone(), u, v)
+                .with_tangents(tangent, bitangent),
+        )
     }
 
     fn bounding_box(&self) -> Aabb {
         self.bbox.clone()
     }
+
+    /// Uniform over the cone the sphere subtends from `origin`. Moving
+    /// spheres are sampled where they are at time 0.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, rng: &mut dyn Rng) -> f64 {
+        let ray = Ray::new(*origin, *direction);
+        if self.hit(&ray, &Interval::new(0.001, INF), rng).is_none() {
+            return 0.0;
+        }
+        let distance_squared = (self.center - *origin).length_squared();
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance_squared)
+            .max(0.0)
+            .sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: &Point3, rng: &mut dyn Rng) -> Vec3 {
+        let direction = self.center - *origin;
+        let uvw = Onb::new(&direction);
+        uvw.transform(&Vec3::random_to_sphere(
+            rng,
+            self.radius,
+            direction.length_squared(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::Sphere;
+    use crate::{Color, Hittable, Interval, Lambertian, Pcg32, Point3, Ray, Vec3, INF};
+
+    #[test]
+    fn tangents_follow_uv() {
+        // A small step along dp/du or dp/dv moves the hit by that step in u
+        // or v alone.
+        let mat = Arc::new(Lambertian::new_from_color(Color::new(0.5, 0.5, 0.5)));
+        let center = Point3::new(1.0, 2.0, 3.0);
+        let sphere = Sphere::new(center, 2.0, mat);
+        let ray = Ray::new(
+            center + Vec3::new(5.0, 3.0, -4.0),
+            Vec3::new(-5.0, -3.0, 4.0),
+        );
+        let mut rng = Pcg32::new(0, 0);
+        let record = sphere
+            .hit(&ray, &Interval::new(0.001, INF), &mut rng)
+            .unwrap();
+        let step = 1e-6;
+        for (dp, du, dv) in [(record.tangent, step, 0.0), (record.bitangent, 0.0, step)] {
+            let moved = (record.point + step * dp - center).unit_vector();
+            let (u, v) = sphere.get_uv(moved);
+            assert!(
+                (u - record.u - du).abs() < 1e-9,
+                "{} vs {}",
+                u - record.u,
+                du
+            );
+            assert!(
+                (v - record.v - dv).abs() < 1e-9,
+                "{} vs {}",
+                v - record.v,
+                dv
+            );
+        }
+    }
 }
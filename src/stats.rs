@@ -0,0 +1,102 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Why a path stopped bouncing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Termination {
+    /// Left the scene and picked up the background.
+    Escaped,
+    /// Hit a surface that doesn't scatter, such as a light.
+    Absorbed,
+    /// Ran out of bounces.
+    MaxDepth,
+    /// Ended by Russian roulette.
+    RussianRoulette,
+}
+
+impl Termination {
+    pub const ALL: [Termination; 4] = [
+        Termination::Escaped,
+        Termination::Absorbed,
+        Termination::MaxDepth,
+        Termination::RussianRoulette,
+    ];
+}
+
+/// How one camera path went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathStats {
+    /// Scattering events along the path.
+    pub bounces: usize,
+    pub termination: Termination,
+}
+
+/// Receives the statistics of every traced path, from every render thread.
+pub trait PathStatsCollector: Send + Sync {
+    fn record(&self, stats: PathStats);
+}
+
+/// Counts paths, bounces and termination reasons.
+#[derive(Debug, Default)]
+pub struct PathStatsSummary {
+    paths: AtomicU64,
+    bounces: AtomicU64,
+    max_bounces: AtomicUsize,
+    terminations: [AtomicU64; 4],
+}
+
+impl PathStatsSummary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn paths(&self) -> u64 {
+        self.paths.load(Ordering::Relaxed)
+    }
+
+    pub fn mean_bounces(&self) -> f64 {
+        self.bounces.load(Ordering::Relaxed) as f64 / self.paths().max(1) as f64
+    }
+
+    pub fn max_bounces(&self) -> usize {
+        self.max_bounces.load(Ordering::Relaxed)
+    }
+
+    /// Paths that ended for `reason`.
+    pub fn terminated(&self, reason: Termination) -> u64 {
+        self.terminations[reason as usize].load(Ordering::Relaxed)
+    }
+}
+
+impl PathStatsCollector for PathStatsSummary {
+    fn record(&self, stats: PathStats) {
+        self.paths.fetch_add(1, Ordering::Relaxed);
+        self.bounces
+            .fetch_add(stats.bounces as u64, Ordering::Relaxed);
+        self.max_bounces.fetch_max(stats.bounces, Ordering::Relaxed);
+        self.terminations[stats.termination as usize].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Display for PathStatsSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} paths, {:.2} bounces on average, {} at most",
+            self.paths(),
+            self.mean_bounces(),
+            self.max_bounces()
+        )?;
+        for reason in Termination::ALL {
+            let count = self.terminated(reason);
+            write!(
+                f,
+                "\n  {:<16} {:>12} ({:5.1}%)",
+                format!("{:?}", reason),
+                count,
+                100.0 * count as f64 / self.paths().max(1) as f64
+            )?;
+        }
+        Ok(())
+    }
+}
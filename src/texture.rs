@@ -1,9 +1,19 @@
-use crate::{Color, Image, Interval, Perlin, Point3};
+use std::path::Path;
+use std::sync::Arc;
 
-pub trait Texture {
+use crate::{Color, Error, Image, Interval, Perlin, Point3, Result, Rng};
+
+pub trait Texture: Send + Sync {
     fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
 }
 
+/// Lets one texture be shared by several materials.
+impl<T: Texture + ?Sized> Texture for Arc<T> {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        (**self).value(u, v, p)
+    }
+}
+
 pub struct SolidColor {
     color: Color,
 }
@@ -69,9 +79,24 @@ pub struct ImageTexture {
 }
 
 impl ImageTexture {
-    pub fn new(image_path: &str) -> Self {
-        Self {
-            image: Image::new(image_path),
+    pub fn new(image_path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self {
+            image: Image::new(image_path)?,
+        })
+    }
+
+    /// Like `new`, but a file that cannot be loaded is rendered as a
+    /// magenta/cyan checkerboard instead, and the error is handed back for
+    /// the caller to report.
+    pub fn new_or_placeholder(image_path: impl AsRef<Path>) -> (Self, Option<Error>) {
+        match Self::new(image_path) {
+            Ok(texture) => (texture, None),
+            Err(err) => (
+                Self {
+                    image: Image::default(),
+                },
+                Some(err),
+            ),
         }
     }
 }
@@ -79,7 +104,12 @@ impl ImageTexture {
 impl Texture for ImageTexture {
     fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
         if self.image.height == 0 {
-            return Color::new(0.0, 1.0, 1.0);
+            let checker = ((u * 8.0).floor() + (v * 8.0).floor()) as i32 % 2 == 0;
+            return if checker {
+                Color::new(1.0, 0.0, 1.0)
+            } else {
+                Color::new(0.0, 1.0, 1.0)
+            };
         }
         let u = Interval::new(0.0, 1.0).clamp(u);
         let v = 1.0 - Interval::new(0.0, 1.0).clamp(v);
@@ -101,9 +131,9 @@ pub struct NoiseTexture {
 }
 
 impl NoiseTexture {
-    pub fn new(scale: f64) -> Self {
+    pub fn new(scale: f64, rng: &mut dyn Rng) -> Self {
         Self {
-            noise: Perlin::new(),
+            noise: Perlin::new(rng),
             scale,
         }
     }
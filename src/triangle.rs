@@ -0,0 +1,248 @@
+use crate::{Aabb, HitRecord, Hittable, Interval, Material, Point3, Ray, Rng, Vec3};
+use std::sync::Arc;
+
+pub struct Triangle {
+    v0: Point3,
+    v1: Point3,
+    v2: Point3,
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    mat: Arc<dyn Material>,
+    normal: Vec3,
+    bbox: Aabb,
+}
+
+impl Triangle {
+    /// Vertices in counter-clockwise order seen from the front side.
+    pub fn new(v0: Point3, v1: Point3, v2: Point3, mat: Arc<dyn Material>) -> Self {
+        let normal = (v1 - v0).cross(&(v2 - v0)).unit_vector();
+        let bbox =
+            Aabb::new_from_aabb(Aabb::new_from_points(v0, v1), Aabb::new_from_points(v2, v2)).pad();
+        Self {
+            v0,
+            v1,
+            v2,
+            normals: None,
+            uvs: None,
+            mat,
+            normal,
+            bbox,
+        }
+    }
+
+    /// Per-vertex normals, interpolated across the face for smooth shading.
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    /// Per-vertex texture coordinates. Without them `u`, `v` are the
+    /// barycentric weights of `v1` and `v2`.
+    pub fn with_uvs(mut self, uvs: [(f64, f64); 3]) -> Self {
+        self.uvs = Some(uvs);
+        self
+    }
+}
+
+/// Barycentric coordinates and distance of a hit.
+pub(crate) struct TriangleHit {
+    pub t: f64,
+    pub b0: f64,
+    pub b1: f64,
+    pub b2: f64,
+}
+
+/// dp/du and dp/dv across a triangle, from its vertices and texture
+/// coordinates. Without texture coordinates `u` and `v` are the barycentric
+/// weights of the second and third vertex.
+pub(crate) fn tangents(positions: [Point3; 3], uvs: Option<[(f64, f64); 3]>) -> (Vec3, Vec3) {
+    let [p0, p1, p2] = positions;
+    let (e1, e2) = (p1 - p0, p2 - p0);
+    let Some([uv0, uv1, uv2]) = uvs else {
+        return (e1, e2);
+    };
+    let (du1, dv1) = (uv1.0 - uv0.0, uv1.1 - uv0.1);
+    let (du2, dv2) = (uv2.0 - uv0.0, uv2.1 - uv0.1);
+    let det = du1 * dv2 - du2 * dv1;
+    if det.abs() < 1e-12 {
+        // Degenerate texture coordinates; fall back to the barycentric ones.
+        return (e1, e2);
+    }
+    ((dv2 * e1 - dv1 * e2) / det, (du1 * e2 - du2 * e1) / det)
+}
+
+/// Watertight ray/triangle intersection (Woop, Benthin and Wald, 2013).
+/// Rays through a shared edge or vertex always hit at least one of the
+/// triangles around it, so meshes have no cracks.
+pub(crate) fn intersect(
+    ray: &Ray,
+    interval: &Interval,
+    v0: Point3,
+    v1: Point3,
+    v2: Point3,
+) -> Option<TriangleHit> {
+    let dir = ray.dir();
+    let kz = (0..3)
+        .max_by(|&a, &b| dir[a].abs().total_cmp(&dir[b].abs()))
+        .unwrap_or(2);
+    let mut kx = (kz + 1) % 3;
+    let mut ky = (kx + 1) % 3;
+    if dir[kz] < 0.0 {
+        std::mem::swap(&mut kx, &mut ky);
+    }
+    let sx = dir[kx] / dir[kz];
+    let sy = dir[ky] / dir[kz];
+    let sz = 1.0 / dir[kz];
+
+    let a = v0 - ray.origin();
+    let b = v1 - ray.origin();
+    let c = v2 - ray.origin();
+    let (ax, ay) = (a[kx] - sx * a[kz], a[ky] - sy * a[kz]);
+    let (bx, by) = (b[kx] - sx * b[kz], b[ky] - sy * b[kz]);
+    let (cx, cy) = (c[kx] - sx * c[kz], c[ky] - sy * c[kz]);
+
+    let u = cx * by - cy * bx;
+    let v = ax * cy - ay * cx;
+    let w = bx * ay - by * ax;
+    if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+        return None;
+    }
+    let det = u + v + w;
+    if det == 0.0 {
+        return None;
+    }
+
+    let t = (u * sz * a[kz] + v * sz * b[kz] + w * sz * c[kz]) / det;
+    if !interval.surrounds(t) {
+        return None;
+    }
+    Some(TriangleHit {
+        t,
+        b0: u / det,
+        b1: v / det,
+        b2: w / det,
+    })
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, interval: &Interval, _rng: &mut dyn Rng) -> Option<HitRecord> {
+        let hit = intersect(ray, interval, self.v0, self.v1, self.v2)?;
+        let (u, v) = match self.uvs {
+            Some([uv0, uv1, uv2]) => (
+                hit.b0 * uv0.0 + hit.b1 * uv1.0 + hit.b2 * uv2.0,
+                hit.b0 * uv0.1 + hit.b1 * uv1.1 + hit.b2 * uv2.1,
+            ),
+            None => (hit.b1, hit.b2),
+        };
+        let (tangent, bitangent) = tangents([self.v0, self.v1, self.v2], self.uvs);
+        let mut record = HitRecord::new(
+            ray,
+            ray.at(hit.t),
+            self.normal,
+            hit.t,
+            self.mat.clone(),
+            u,
+            v,
+        )
+        .with_tangents(tangent, bitangent);
+        if let Some([n0, n1, n2]) = self.normals {
+            let shading = (hit.b0 * n0 + hit.b1 * n1 + hit.b2 * n2).unit_vector();
+            record.normal = if record.front_face { shading } else { -shading };
+        }
+        Some(record)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::Triangle;
+    use crate::{Color, Hittable, Interval, Lambertian, Pcg32, Point3, Ray, Vec3, INF};
+
+    fn triangle(v0: Point3, v1: Point3, v2: Point3) -> Triangle {
+        let mat = Arc::new(Lambertian::new_from_color(Color::new(0.5, 0.5, 0.5)));
+        Triangle::new(v0, v1, v2, mat)
+    }
+
+    #[test]
+    fn barycentric_uv() {
+        let tri = triangle(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+        );
+        let ray = Ray::new(Point3::new(0.25, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rng = Pcg32::new(0, 0);
+        let rec = tri.hit(&ray, &Interval::new(0.001, INF), &mut rng).unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-12);
+        assert!((rec.u - 0.25).abs() < 1e-12);
+        assert!((rec.v - 0.5).abs() < 1e-12);
+        assert!(rec.front_face);
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+
+        let miss = Ray::new(Point3::new(0.75, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(tri
+            .hit(&miss, &Interval::new(0.001, INF), &mut rng)
+            .is_none());
+    }
+
+    #[test]
+    fn shared_edge_is_watertight() {
+        let a = Point3::new(0.0, 0.0, 0.0);
+        let b = Point3::new(1.0, 0.0, 0.0);
+        let c = Point3::new(0.0, 1.0, 0.0);
+        let d = Point3::new(1.0, 1.0, 0.0);
+        let first = triangle(a, b, c);
+        let second = triangle(b, d, c);
+        let mut rng = Pcg32::new(0, 0);
+        // Rays aimed at points on the diagonal shared by both triangles.
+        for i in 1..100 {
+            let s = i as f64 / 100.0;
+            let target = Point3::new(s, 1.0 - s, 0.0);
+            let origin = Point3::new(0.3, 0.1, 1.0) + 0.7 * target;
+            let ray = Ray::new(origin, target - origin);
+            let interval = Interval::new(0.001, INF);
+            assert!(
+                first.hit(&ray, &interval, &mut rng).is_some()
+                    || second.hit(&ray, &interval, &mut rng).is_some()
+            );
+        }
+    }
+
+    #[test]
+    fn interpolated_normals_face_the_ray() {
+        let n = Vec3::new(0.0, 0.0, 1.0);
+        let tri = triangle(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+        )
+        .with_normals([n, n, Vec3::new(0.0, 1.0, 1.0).unit_vector()]);
+        let ray = Ray::new(Point3::new(0.2, 0.2, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        let mut rng = Pcg32::new(0, 0);
+        let rec = tri.hit(&ray, &Interval::new(0.001, INF), &mut rng).unwrap();
+        assert!(!rec.front_face);
+        assert!(rec.normal.z() < 0.0);
+    }
+
+    #[test]
+    fn tangents_follow_uv() {
+        // Texture coordinates that run u up the y edge and v along x.
+        let tri = triangle(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(2.0, 0.0, 0.0),
+            Point3::new(0.0, 3.0, 0.0),
+        )
+        .with_uvs([(0.0, 0.0), (0.0, 1.0), (1.0, 0.0)]);
+        let ray = Ray::new(Point3::new(0.5, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rng = Pcg32::new(0, 0);
+        let rec = tri.hit(&ray, &Interval::new(0.001, INF), &mut rng).unwrap();
+        assert!((rec.tangent - Vec3::new(0.0, 3.0, 0.0)).near_zero());
+        assert!((rec.bitangent - Vec3::new(2.0, 0.0, 0.0)).near_zero());
+    }
+}
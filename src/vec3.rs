@@ -1,6 +1,8 @@
 use std::ops::{self, Neg};
 
-use crate::{rand, rand_range};
+use serde::{Deserialize, Deserializer};
+
+use crate::{Rng, PI};
 
 type Elem = [f64; 3];
 
@@ -56,33 +58,33 @@ impl Vec3 {
         *self / self.length()
     }
 
-    pub fn random() -> Self {
-        Self::new(rand(), rand(), rand())
+    pub fn random(rng: &mut dyn Rng) -> Self {
+        Self::new(rng.next_f64(), rng.next_f64(), rng.next_f64())
     }
 
-    pub fn random_range(min: f64, max: f64) -> Self {
+    pub fn random_range(rng: &mut dyn Rng, min: f64, max: f64) -> Self {
         Self::new(
-            rand_range(min, max),
-            rand_range(min, max),
-            rand_range(min, max),
+            rng.range(min, max),
+            rng.range(min, max),
+            rng.range(min, max),
         )
     }
 
-    pub fn random_in_unit_sphere() -> Self {
+    pub fn random_in_unit_sphere(rng: &mut dyn Rng) -> Self {
         loop {
-            let v = Self::random_range(-1.0, 1.0);
+            let v = Self::random_range(rng, -1.0, 1.0);
             if v.length_squared() < 1.0 {
                 break v;
             }
         }
     }
 
-    pub fn random_unit_vector() -> Self {
-        Self::random_in_unit_sphere().unit_vector()
+    pub fn random_unit_vector(rng: &mut dyn Rng) -> Self {
+        Self::random_in_unit_sphere(rng).unit_vector()
     }
 
-    pub fn random_on_hemisphere(normal: &Self) -> Self {
-        let v = Self::random_unit_vector();
+    pub fn random_on_hemisphere(rng: &mut dyn Rng, normal: &Self) -> Self {
+        let v = Self::random_unit_vector(rng);
         if v.dot(normal) > 0.0 {
             v
         } else {
@@ -90,6 +92,27 @@ impl Vec3 {
         }
     }
 
+    /// A direction about +z with density `cos(theta) / pi`.
+    pub fn random_cosine_direction(rng: &mut dyn Rng) -> Self {
+        let r1 = rng.next_f64();
+        let r2 = rng.next_f64();
+        let phi = 2.0 * PI * r1;
+        let x = phi.cos() * r2.sqrt();
+        let y = phi.sin() * r2.sqrt();
+        Self::new(x, y, (1.0 - r2).sqrt())
+    }
+
+    /// A direction about +z, uniform over the cone that a sphere of `radius`
+    /// at `distance_squared` subtends.
+    pub fn random_to_sphere(rng: &mut dyn Rng, radius: f64, distance_squared: f64) -> Self {
+        let r1 = rng.next_f64();
+        let r2 = rng.next_f64();
+        let z = 1.0 + r2 * ((1.0 - radius * radius / distance_squared).max(0.0).sqrt() - 1.0);
+        let phi = 2.0 * PI * r1;
+        let sin = (1.0 - z * z).max(0.0).sqrt();
+        Self::new(phi.cos() * sin, phi.sin() * sin, z)
+    }
+
     pub fn near_zero(&self) -> bool {
         let s = 1e-8;
         (self[0].abs() < s) && (self[1].abs() < s) && (self[2].abs() < s)
@@ -106,9 +129,9 @@ impl Vec3 {
         r_out_perp + r_out_parallel
     }
 
-    pub fn random_in_unit_disk() -> Self {
+    pub fn random_in_unit_disk(rng: &mut dyn Rng) -> Self {
         loop {
-            let p = Self::new(rand_range(-1.0, 1.0), rand_range(-1.0, 1.0), 0.0);
+            let p = Self::new(rng.range(-1.0, 1.0), rng.range(-1.0, 1.0), 0.0);
             if p.length_squared() < 1.0 {
                 break p;
             }
@@ -288,6 +311,14 @@ impl ops::IndexMut<usize> for Vec3 {
     }
 }
 
+/// Vectors are written as `[x, y, z]` in scene files.
+impl<'de> Deserialize<'de> for Vec3 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let [x, y, z] = <[f64; 3]>::deserialize(deserializer)?;
+        Ok(Self::new(x, y, z))
+    }
+}
+
 pub type Point3 = Vec3;
 
 mod tests {